@@ -0,0 +1,108 @@
+use crate::*;
+
+/// A tile covering a rectangle of cells, drawn from one image. Each cell of it is a tile of its own to the solver,
+/// called a part, but parts can only sit next to the parts they're next to in the big tile,
+/// and collapsing one part places the whole big tile at once
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigTile {
+    /// The texture of the whole image, which is split evenly between the parts
+    pub texture: &'static str,
+    pub width: i32,
+    pub height: i32,
+    /// The weight of each part
+    pub weight: i32,
+    /// The connections around the outside, up right down left. Up and down are listed left to right,
+    /// and left and right top to bottom, so each side has one connection per cell along it
+    pub sides: [Vec<Connection>; 4],
+}
+
+/// Where a part is, as the big tile's position in Tileset::big_tiles and the cell it covers from the big tile's top left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Part {
+    pub big: usize,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl BigTile {
+    /// The name of one part's texture, which is only used to tell the parts apart. They're drawn from the big tile's texture
+    pub fn part_texture (&self, x: i32, y: i32) -> &'static str {
+        return tileset::intern(&format!("{} {},{}", self.texture, x, y));
+    }
+
+    /// Whether the numbers of connections match the big tile's size
+    pub fn is_valid (&self) -> bool {
+        let lengths = [self.width, self.height, self.width, self.height];
+        return self.width > 0 && self.height > 0 && self.sides.iter().zip(lengths).all(|(side, length)| side.len() == length as usize);
+    }
+
+    /// Every part, row by row from the top left. The sides of a part facing another part are never compared by connection,
+    /// but they need one, so they're given the first connection of the big tile's top edge
+    pub fn parts (&self) -> Vec<TileChoice> {
+        let inside = self.sides[0][0];
+        let mut parts = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let up = if y == 0 {self.sides[0][x as usize]} else {inside};
+                let right = if x == self.width - 1 {self.sides[1][y as usize]} else {inside};
+                let down = if y == self.height - 1 {self.sides[2][x as usize]} else {inside};
+                let left = if x == 0 {self.sides[3][y as usize]} else {inside};
+                parts.push(TileChoice {
+                    connections: [up, right, down, left],
+                    weight: self.weight,
+                    texture: self.part_texture(x, y),
+                    flipx: false,
+                    flipy: false,
+                    rot90: false,
+                });
+            }
+        }
+        return parts;
+    }
+
+    /// Whether a part's side in direction faces another part of the same big tile
+    pub fn inside (&self, part: Part, direction: usize) -> bool {
+        return match direction {
+            0 => part.y > 0,
+            1 => part.x < self.width - 1,
+            2 => part.y < self.height - 1,
+            _ => part.x > 0,
+        };
+    }
+
+    /// The part of a texture covered by one part, given where the whole image is
+    pub fn part_rect (&self, image: Rect, part: Part) -> Rect {
+        let (width, height) = (image.w / self.width as f32, image.h / self.height as f32);
+        return Rect::new(image.x + part.x as f32 * width, image.y + part.y as f32 * height, width, height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn door () -> BigTile {
+        let (a, b) = (Connection::ALL[0], Connection::ALL[1]);
+        return BigTile {texture: "Test door", width: 2, height: 2, weight: 4, sides: [vec![a, b], vec![b, a], vec![a, a], vec![b, b]]};
+    }
+
+    #[test]
+    fn parts_are_listed_row_by_row_with_the_outside_connections () {
+        let (a, b) = (Connection::ALL[0], Connection::ALL[1]);
+        let parts: Vec<(&str, [Connection; 4], i32)> = door().parts().iter().map(|part| (part.texture, part.connections, part.weight)).collect();
+        // Sides facing another part get the first connection of the top edge, which is a
+        assert_eq!(parts, vec![
+            ("Test door 0,0", [a, a, a, b], 4),
+            ("Test door 1,0", [b, b, a, a], 4),
+            ("Test door 0,1", [a, a, a, b], 4),
+            ("Test door 1,1", [a, a, a, a], 4),
+        ]);
+    }
+
+    #[test]
+    fn parts_are_drawn_from_their_share_of_the_image () {
+        let image = Rect::new(10.0, 20.0, 64.0, 32.0);
+        assert_eq!(door().part_rect(image, Part {big: 0, x: 0, y: 0}), Rect::new(10.0, 20.0, 32.0, 16.0));
+        assert_eq!(door().part_rect(image, Part {big: 0, x: 1, y: 1}), Rect::new(42.0, 36.0, 32.0, 16.0));
+    }
+}
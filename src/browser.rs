@@ -0,0 +1,146 @@
+use crate::*;
+use crate::renderers::atlas::Atlas;
+use crate::renderers::texture::draw_tile_opt;
+use crate::inspector::{neighbour, opposite, option_name, DIRECTION_NAMES};
+use crate::editor::button;
+
+const FONT_SIZE: f32 = 16.0;
+/// The most pixels a cell of the matrix takes up, they shrink to fit big tilesets on screen
+const MAX_CELL: f32 = 24.0;
+const PAIR_TILE: f32 = 32.0;
+
+/// A screen showing which tiles the tileset in use lets sit next to each other.
+/// It only reads the tileset, changing it is left to the editor
+pub struct Browser {
+    pub open: bool,
+    /// The index of the tile whose neighbours are shown
+    selected: usize,
+    /// Which side of the selected tile the neighbours are on, and which matrix is shown
+    direction: usize,
+}
+
+impl Browser {
+    pub fn new () -> Self {
+        return Self {
+            open: false,
+            selected: 0,
+            direction: 1,
+        };
+    }
+
+    /// Handles input and draws the browser
+    pub fn update (&mut self, atlas: &Atlas) {
+        let tileset = tileset::current();
+        self.selected = min(self.selected, tileset.tiles.len() - 1);
+        clear_background(Color::new(0.1, 0.1, 0.1, 1.0));
+
+        for (direction, name) in DIRECTION_NAMES.iter().enumerate() {
+            if button(Rect::new(8.0 + direction as f32 * 70.0, 8.0, 66.0, 24.0), name, self.direction == direction) {
+                self.direction = direction;
+            }
+        }
+        draw_text("Rows are tiles, columns are the neighbours they allow. J to close", 300.0, 24.0, FONT_SIZE, LIGHTGRAY);
+
+        let matrix_bottom = self.draw_matrix(&tileset, atlas);
+        self.draw_neighbours(&tileset, atlas);
+        self.draw_problems(&tileset, matrix_bottom);
+    }
+
+    /// The compatibility matrix for the chosen direction, with each tile drawn along the top and left.
+    /// Dead tiles have their row header outlined in red, and cells whose rule only goes one way are outlined in orange.
+    /// Returns where the bottom of the matrix is
+    fn draw_matrix (&mut self, tileset: &Tileset, atlas: &Atlas) -> f32 {
+        let count = tileset.tiles.len();
+        let left = 8.0;
+        let top = 48.0;
+        let available = (screen_width() / 2.0).min(screen_height() - 160.0) - top;
+        let cell = (available / (count + 1) as f32).min(MAX_CELL).floor().max(2.0);
+        let adjacency = tileset.adjacency(self.direction);
+        let mouse = Vec2::from(mouse_position());
+        let mut hovered = None;
+
+        for (i, tileopt) in tileset.tiles.iter().enumerate() {
+            let offset = (i + 1) as f32 * cell;
+            draw_tile_opt(left + offset, top, Vec2::splat(cell), tileopt, atlas);
+            draw_tile_opt(left, top + offset, Vec2::splat(cell), tileopt, atlas);
+            if !adjacency[i].contains(&true) {
+                draw_rectangle_lines(left, top + offset, cell, cell, 2.0, RED);
+            }
+            if i == self.selected {
+                draw_rectangle_lines(left, top + offset, cell * (count + 1) as f32, cell, 2.0, YELLOW);
+            }
+            for (j, other) in tileset.tiles.iter().enumerate() {
+                let rect = Rect::new(left + (j + 1) as f32 * cell, top + offset, cell, cell);
+                let color = if adjacency[i][j] {GREEN} else {DARKGRAY};
+                draw_rectangle(rect.x + 1.0, rect.y + 1.0, rect.w - 2.0, rect.h - 2.0, color);
+                if adjacency[i][j] != tileset.allows(other, tileopt, opposite(self.direction)) {
+                    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, ORANGE);
+                }
+                if rect.contains(mouse) {
+                    hovered = Some((i, j));
+                }
+            }
+            let row = Rect::new(left, top + offset, cell * (count + 1) as f32, cell);
+            if row.contains(mouse) && is_mouse_button_pressed(MouseButton::Left) {
+                self.selected = i;
+            }
+        }
+
+        let bottom = top + (count + 1) as f32 * cell;
+        if let Some((i, j)) = hovered {
+            let verdict = if adjacency[i][j] {"allows"} else {"doesn't allow"};
+            let line = format!("{} {} {} to its {}", option_name(&tileset.tiles[i]), verdict, option_name(&tileset.tiles[j]), DIRECTION_NAMES[self.direction]);
+            draw_text(&line, left, bottom + 20.0, FONT_SIZE, WHITE);
+        }
+        return bottom + 24.0;
+    }
+
+    /// The selected tile next to every tile it allows in the chosen direction, so the pairs can be seen as they'd be placed
+    fn draw_neighbours (&self, tileset: &Tileset, atlas: &Atlas) {
+        let left = screen_width() / 2.0 + 16.0;
+        let top = 48.0;
+        let selected = &tileset.tiles[self.selected];
+        draw_text(format!("Allowed {} of {}:", DIRECTION_NAMES[self.direction], option_name(selected)), left, top + 12.0, FONT_SIZE, WHITE);
+
+        // Where the neighbour goes relative to the selected tile, in tiles
+        let (dx, dy) = neighbour(0, 0, self.direction);
+        let origin = Vec2::new(min(0, dx) as f32, min(0, dy) as f32) * -PAIR_TILE;
+        let pair_size = Vec2::new((1 + dx.abs()) as f32, (1 + dy.abs()) as f32) * PAIR_TILE + Vec2::splat(8.0);
+        let columns = max(1, ((screen_width() - left - 8.0) / pair_size.x) as usize);
+
+        let allowed: Vec<&TileChoice> = tileset.tiles.iter().filter(|other| tileset.allows(selected, other, self.direction)).collect();
+        if allowed.is_empty() {
+            draw_text("Nothing, this tile is dead on this side", left, top + 36.0, FONT_SIZE, RED);
+        }
+        for (n, other) in allowed.iter().enumerate() {
+            let corner = Vec2::new(left + (n % columns) as f32 * pair_size.x, top + 24.0 + (n / columns) as f32 * pair_size.y) + origin;
+            draw_tile_opt(corner.x, corner.y, Vec2::splat(PAIR_TILE), selected, atlas);
+            draw_tile_opt(corner.x + dx as f32 * PAIR_TILE, corner.y + dy as f32 * PAIR_TILE, Vec2::splat(PAIR_TILE), other, atlas);
+            draw_rectangle_lines(corner.x, corner.y, PAIR_TILE, PAIR_TILE, 1.0, YELLOW);
+        }
+    }
+
+    /// Lists dead tiles and rules that only go one way, across every direction
+    fn draw_problems (&self, tileset: &Tileset, top: f32) {
+        let mut lines = Vec::new();
+        for (i, direction) in tileset.dead_tiles() {
+            lines.push((format!("Dead: {} has no neighbour {}", option_name(&tileset.tiles[i]), DIRECTION_NAMES[direction]), RED));
+        }
+        for (i, j, direction) in tileset.asymmetric_rules() {
+            let (a, b) = (option_name(&tileset.tiles[i]), option_name(&tileset.tiles[j]));
+            lines.push((format!("One way: {} allows {} {} of it, but not the other way round", a, b, DIRECTION_NAMES[direction]), ORANGE));
+        }
+        if lines.is_empty() {
+            lines.push(("No dead tiles or one way rules".to_string(), GREEN));
+        }
+        let max_lines = max(1, ((screen_height() - top) / FONT_SIZE) as usize);
+        if lines.len() > max_lines {
+            let hidden = lines.len() - max_lines + 1;
+            lines.truncate(max_lines - 1);
+            lines.push((format!("... and {} more", hidden), WHITE));
+        }
+        for (n, (line, color)) in lines.iter().enumerate() {
+            draw_text(line, 8.0, top + (n + 1) as f32 * FONT_SIZE, FONT_SIZE, *color);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::*;
+
+/// The number of screen pixels a tile takes up at zoom 1
+pub const TILE_PIXELS: f32 = 32.0;
+const MIN_ZOOM: f32 = 0.025;
+const MAX_ZOOM: f32 = 30.0;
+/// The gap left around the grid when fitting it to the window
+const FIT_MARGIN: f32 = 10.0;
+
+/// Where the grid is being viewed from. World positions are measured in tiles, with the grid's top left corner at 0, 0.
+/// Only changes what is shown, never the grid itself
+pub struct Camera {
+    /// The world position at the top left of the screen
+    pub offset: Vec2,
+    /// How much bigger than TILE_PIXELS each tile is drawn
+    pub zoom: f32,
+    /// Where the mouse was last frame, while middle dragging
+    drag_from: Option<Vec2>,
+}
+
+impl Camera {
+    pub fn new () -> Self {
+        return Self {
+            offset: Vec2::new(-FIT_MARGIN / TILE_PIXELS, -FIT_MARGIN / TILE_PIXELS),
+            zoom: 1.0,
+            drag_from: None,
+        };
+    }
+
+    /// The number of screen pixels each tile takes up
+    pub fn tile_pixels (&self) -> f32 {
+        return TILE_PIXELS * self.zoom;
+    }
+
+    pub fn screen_to_world (&self, screen: Vec2) -> Vec2 {
+        return screen / self.tile_pixels() + self.offset;
+    }
+
+    pub fn world_to_screen (&self, world: Vec2) -> Vec2 {
+        return (world - self.offset) * self.tile_pixels();
+    }
+
+    /// The index of the tile under a point on the screen, which may be outside the grid
+    pub fn screen_to_tile (&self, screen: Vec2) -> (i32, i32) {
+        let world = self.screen_to_world(screen);
+        return (world.x.floor() as i32, world.y.floor() as i32);
+    }
+
+    /// The part of the world which is on screen
+    pub fn visible_rect (&self) -> Rect {
+        return Rect::new(self.offset.x, self.offset.y, screen_width() / self.tile_pixels(), screen_height() / self.tile_pixels());
+    }
+
+    /// Zooms by factor while keeping the world position under anchor, a point on the screen, in place
+    pub fn zoom_at (&mut self, anchor: Vec2, factor: f32) {
+        let anchored = self.screen_to_world(anchor);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = anchored - anchor / self.tile_pixels();
+    }
+
+    /// Moves the view by a distance in screen pixels
+    pub fn pan (&mut self, screen_delta: Vec2) {
+        self.offset -= screen_delta / self.tile_pixels();
+    }
+
+    /// Zooms and pans so the whole grid fits in the window, centred
+    pub fn fit (&mut self, grid: &TileGrid) {
+        let zoom_x = (screen_width() - FIT_MARGIN * 2.0) / (grid.width as f32 * TILE_PIXELS);
+        let zoom_y = (screen_height() - FIT_MARGIN * 2.0) / (grid.height as f32 * TILE_PIXELS);
+        self.zoom = zoom_x.min(zoom_y).clamp(MIN_ZOOM, MAX_ZOOM);
+        let grid_size = Vec2::new(grid.width as f32, grid.height as f32);
+        let screen_size = Vec2::new(screen_width(), screen_height());
+        self.offset = grid_size / 2.0 - screen_size / 2.0 / self.tile_pixels();
+    }
+
+    /// Middle drag and arrow keys to pan, scroll wheel to zoom at the cursor, = and - to zoom at the centre, Z to fit the grid
+    pub fn handle_input (&mut self, grid: &TileGrid) {
+        let mouse = Vec2::from(mouse_position());
+        if is_mouse_button_down(MouseButton::Middle) {
+            if let Some(drag_from) = self.drag_from {
+                self.pan(mouse - drag_from);
+            }
+            self.drag_from = Some(mouse);
+        }
+        else {
+            self.drag_from = None;
+        }
+
+        let scroll = mouse_wheel().1;
+        if scroll > 0.0 {self.zoom_at(mouse, 1.1);}
+        if scroll < 0.0 {self.zoom_at(mouse, 1.0 / 1.1);}
+        let centre = Vec2::new(screen_width() / 2.0, screen_height() / 2.0);
+        if is_key_down(KeyCode::Equal) {self.zoom_at(centre, 1.01);}
+        if is_key_down(KeyCode::Minus) {self.zoom_at(centre, 0.99);}
+
+        // Shift is kept for shifting the grid itself
+        if !is_key_down(KeyCode::LeftShift) {
+            const PAN_SPEED: f32 = 8.0; // pixels per frame
+            if is_key_down(KeyCode::Up) {self.pan(Vec2::new(0.0, PAN_SPEED));}
+            if is_key_down(KeyCode::Down) {self.pan(Vec2::new(0.0, -PAN_SPEED));}
+            if is_key_down(KeyCode::Left) {self.pan(Vec2::new(PAN_SPEED, 0.0));}
+            if is_key_down(KeyCode::Right) {self.pan(Vec2::new(-PAN_SPEED, 0.0));}
+        }
+
+        if is_key_pressed(KeyCode::Z) {self.fit(grid);}
+    }
+}
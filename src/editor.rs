@@ -0,0 +1,290 @@
+use crate::*;
+use crate::renderers::atlas::Atlas;
+use crate::renderers::texture::draw_tile_opt;
+use crate::inspector::{option_name, DIRECTION_NAMES};
+use crate::tileset::{connection_name, rotate_clockwise};
+
+/// The heaviest weight the slider goes up to
+const MAX_WEIGHT: i32 = 200;
+const ROW_HEIGHT: f32 = 40.0;
+const LIST_WIDTH: f32 = 260.0;
+const BIG_TILE: f32 = 128.0;
+const FONT_SIZE: f32 = 16.0;
+
+/// A screen for editing the tileset while the app is running. Changes are made to a copy,
+/// which replaces the tileset in use and is written to the tileset file when saved
+pub struct Editor {
+    pub open: bool,
+    tileset: Tileset,
+    /// The index of the tile being edited, which is never a part of a big tile
+    selected: usize,
+    /// The side of the selected tile which clicking a connection in the palette will change
+    selected_side: Option<usize>,
+    /// How far down the tile list has been scrolled, in pixels
+    scroll: f32,
+    dragging_weight: bool,
+    /// Shown at the bottom of the screen, like whether saving worked
+    message: String,
+}
+
+/// Draws a button and returns whether it was clicked this frame
+pub fn button (rect: Rect, label: &str, active: bool) -> bool {
+    let hovered = rect.contains(Vec2::from(mouse_position()));
+    let background = if active {DARKGREEN} else if hovered {GRAY} else {DARKGRAY};
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, background);
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, WHITE);
+    draw_text(label, rect.x + 4.0, rect.y + rect.h / 2.0 + 5.0, FONT_SIZE, WHITE);
+    return hovered && is_mouse_button_pressed(MouseButton::Left);
+}
+
+impl Editor {
+    pub fn new () -> Self {
+        return Self {
+            open: false,
+            tileset: (*tileset::current()).clone(),
+            selected: 0,
+            selected_side: None,
+            scroll: 0.0,
+            dragging_weight: false,
+            message: String::new(),
+        };
+    }
+
+    /// Opens the editor on a fresh copy of the tileset in use
+    pub fn show (&mut self) {
+        self.open = true;
+        self.tileset = (*tileset::current()).clone();
+        self.select_near(self.selected);
+        self.message = String::new();
+    }
+
+    /// The indices of the tiles that can be edited. Parts of big tiles are left out, since they're made from their big tile
+    /// and aren't saved as tiles, so any change to them would be lost
+    fn editable (&self) -> Vec<usize> {
+        return (0..self.tileset.tiles.len()).filter(|i| self.tileset.part(self.tileset.tiles[*i].texture).is_none()).collect();
+    }
+
+    /// Selects the editable tile at index, or the nearest one before it if that isn't one
+    fn select_near (&mut self, index: usize) {
+        let editable = self.editable();
+        self.selected = editable.iter().rev().copied().find(|i| *i <= index).or(editable.first().copied()).unwrap_or(0);
+    }
+
+    /// Handles input and draws the editor, returning true if the tileset was saved and put into use this frame
+    pub fn update (&mut self, atlas: &Atlas) -> bool {
+        clear_background(Color::new(0.1, 0.1, 0.1, 1.0));
+        let editable = self.editable();
+        self.draw_tile_list(atlas, &editable);
+        if editable.is_empty() {
+            draw_text("Every tile is part of a big tile, which can't be edited here", LIST_WIDTH + 20.0, 90.0, FONT_SIZE, WHITE);
+        }
+        else {
+            self.draw_selected_tile(atlas);
+            self.draw_palette();
+            self.draw_weight_slider();
+            self.draw_texture_picker(atlas);
+            self.draw_rotations(atlas);
+        }
+        self.draw_connection_matrix();
+
+        let mut saved = false;
+        let top = 8.0;
+        let right = screen_width() - 8.0;
+        if button(Rect::new(right - 80.0, top, 80.0, 24.0), "Save", false) {
+            match self.tileset.save(TILESET_PATH) {
+                Ok(()) => {
+                    tileset::set_current(self.tileset.clone());
+                    self.message = format!("Saved to {}, the grid has been reset to use it", TILESET_PATH);
+                    saved = true;
+                }
+                Err(error) => self.message = format!("Couldn't save to {}: {}", TILESET_PATH, error),
+            }
+        }
+        if button(Rect::new(right - 190.0, top, 100.0, 24.0), "Remove tile", false) && editable.len() > 1 {
+            self.tileset.tiles.remove(self.selected);
+            self.select_near(self.selected);
+        }
+        if button(Rect::new(right - 290.0, top, 90.0, 24.0), "Copy tile", false) && !editable.is_empty() {
+            let copy = self.tileset.tiles[self.selected];
+            self.tileset.tiles.insert(self.selected + 1, copy);
+            self.selected += 1;
+        }
+        draw_text("Tab to close without saving", LIST_WIDTH + 20.0, 24.0, FONT_SIZE, LIGHTGRAY);
+        draw_text(&self.message, LIST_WIDTH + 20.0, screen_height() - 10.0, FONT_SIZE, WHITE);
+        return saved;
+    }
+
+    /// Every editable tile down the left side, scrolled with the mouse wheel. Clicking one selects it
+    fn draw_tile_list (&mut self, atlas: &Atlas, editable: &[usize]) {
+        let mouse = Vec2::from(mouse_position());
+        if mouse.x < LIST_WIDTH {
+            self.scroll -= mouse_wheel().1.signum() * ROW_HEIGHT;
+        }
+        let max_scroll = (editable.len() as f32 * ROW_HEIGHT - screen_height()).max(0.0);
+        self.scroll = self.scroll.clamp(0.0, max_scroll);
+
+        draw_rectangle(0.0, 0.0, LIST_WIDTH, screen_height(), BLACK);
+        for (row_index, &i) in editable.iter().enumerate() {
+            let tileopt = &self.tileset.tiles[i];
+            let y = row_index as f32 * ROW_HEIGHT - self.scroll;
+            if y + ROW_HEIGHT < 0.0 || y > screen_height() {
+                continue;
+            }
+            let row = Rect::new(0.0, y, LIST_WIDTH, ROW_HEIGHT);
+            if i == self.selected {
+                draw_rectangle(row.x, row.y, row.w, row.h, DARKBLUE);
+            }
+            if row.contains(mouse) && is_mouse_button_pressed(MouseButton::Left) {
+                self.selected = i;
+                self.selected_side = None;
+            }
+            draw_tile_opt(8.0, y + 4.0, Vec2::splat(32.0), tileopt, atlas);
+            draw_text(option_name(tileopt), 48.0, y + 18.0, FONT_SIZE, WHITE);
+            draw_text(format!("weight {}", tileopt.weight), 48.0, y + 34.0, FONT_SIZE, LIGHTGRAY);
+        }
+    }
+
+    /// Where the big copy of the selected tile is drawn
+    fn big_tile_rect (&self) -> Rect {
+        return Rect::new(LIST_WIDTH + 160.0, 90.0, BIG_TILE, BIG_TILE);
+    }
+
+    /// The selected tile drawn large with its connection on each side. Clicking a side selects it for the palette
+    fn draw_selected_tile (&mut self, atlas: &Atlas) {
+        let rect = self.big_tile_rect();
+        let tileopt = self.tileset.tiles[self.selected];
+        draw_tile_opt(rect.x, rect.y, rect.size(), &tileopt, atlas);
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, WHITE);
+        for direction in 0..4 {
+            let label = connection_name(tileopt.connections[direction]);
+            let width = measure_text(&label, None, FONT_SIZE as u16, 1.0).width + 8.0;
+            let side = match direction {
+                0 => Rect::new(rect.center().x - width / 2.0, rect.y - 28.0, width, 24.0),
+                1 => Rect::new(rect.right() + 4.0, rect.center().y - 12.0, width, 24.0),
+                2 => Rect::new(rect.center().x - width / 2.0, rect.bottom() + 4.0, width, 24.0),
+                _ => Rect::new(rect.x - width - 4.0, rect.center().y - 12.0, width, 24.0),
+            };
+            if button(side, &label, self.selected_side == Some(direction)) {
+                self.selected_side = if self.selected_side == Some(direction) {None} else {Some(direction)};
+            }
+        }
+    }
+
+    /// Every connection, clicking one sets it on the selected side
+    fn draw_palette (&mut self) {
+        let top = self.big_tile_rect().bottom() + 40.0;
+        let left = LIST_WIDTH + 20.0;
+        let prompt = match self.selected_side {
+            Some(direction) => format!("Pick the {} connection:", DIRECTION_NAMES[direction]),
+            None => "Click a side of the tile to change its connection".to_string(),
+        };
+        draw_text(&prompt, left, top, FONT_SIZE, WHITE);
+        for (i, connection) in Connection::ALL.iter().enumerate() {
+            let rect = Rect::new(left + (i % 5) as f32 * 100.0, top + 8.0 + (i / 5) as f32 * 28.0, 96.0, 24.0);
+            let current = self.selected_side.map(|direction| self.tileset.tiles[self.selected].connections[direction] == *connection).unwrap_or(false);
+            if button(rect, &connection_name(*connection), current) {
+                if let Some(direction) = self.selected_side {
+                    self.tileset.tiles[self.selected].connections[direction] = *connection;
+                }
+            }
+        }
+    }
+
+    /// A bar that sets the selected tile's weight when dragged along
+    fn draw_weight_slider (&mut self) {
+        let top = self.big_tile_rect().bottom() + 120.0;
+        let bar = Rect::new(LIST_WIDTH + 20.0, top, 400.0, 16.0);
+        let weight = &mut self.tileset.tiles[self.selected].weight;
+        let mouse = Vec2::from(mouse_position());
+        if is_mouse_button_pressed(MouseButton::Left) && bar.contains(mouse) {
+            self.dragging_weight = true;
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.dragging_weight = false;
+        }
+        if self.dragging_weight {
+            *weight = (((mouse.x - bar.x) / bar.w).clamp(0.0, 1.0) * MAX_WEIGHT as f32).round() as i32;
+        }
+        draw_text(format!("Weight: {}", weight), bar.x, bar.y - 6.0, FONT_SIZE, WHITE);
+        draw_rectangle(bar.x, bar.y, bar.w, bar.h, DARKGRAY);
+        let filled = (*weight as f32 / MAX_WEIGHT as f32).clamp(0.0, 1.0) * bar.w;
+        draw_rectangle(bar.x, bar.y, filled, bar.h, SKYBLUE);
+        if button(Rect::new(bar.right() + 8.0, bar.y - 4.0, 24.0, 24.0), "-", false) {
+            *weight = max(0, *weight - 1);
+        }
+        if button(Rect::new(bar.right() + 36.0, bar.y - 4.0, 24.0, 24.0), "+", false) {
+            *weight += 1;
+        }
+    }
+
+    /// Buttons to step through the textures in the atlas and the ones with variants, and to flip the texture
+    fn draw_texture_picker (&mut self, atlas: &Atlas) {
+        let top = self.big_tile_rect().bottom() + 150.0;
+        let left = LIST_WIDTH + 20.0;
+        let mut keys = atlas.keys();
+        keys.extend(self.tileset.variants.keys());
+        keys.sort();
+        keys.dedup();
+        let tileopt = &mut self.tileset.tiles[self.selected];
+        let index = keys.iter().position(|key| *key == tileopt.texture).unwrap_or(0);
+        if button(Rect::new(left, top, 24.0, 24.0), "<", false) && !keys.is_empty() {
+            tileopt.texture = keys[(index + keys.len() - 1) % keys.len()];
+        }
+        draw_text(format!("Texture: {}", tileopt.texture), left + 32.0, top + 17.0, FONT_SIZE, WHITE);
+        if button(Rect::new(left + 220.0, top, 24.0, 24.0), ">", false) && !keys.is_empty() {
+            tileopt.texture = keys[(index + 1) % keys.len()];
+        }
+        // These only change the texture, turn the tile with the rotations below to move its connections too
+        if button(Rect::new(left + 260.0, top, 60.0, 24.0), "flipx", tileopt.flipx) {
+            tileopt.flipx = !tileopt.flipx;
+        }
+        if button(Rect::new(left + 324.0, top, 60.0, 24.0), "flipy", tileopt.flipy) {
+            tileopt.flipy = !tileopt.flipy;
+        }
+        if button(Rect::new(left + 388.0, top, 60.0, 24.0), "rot90", tileopt.rot90) {
+            tileopt.rot90 = !tileopt.rot90;
+        }
+    }
+
+    /// The selected tile turned each of the four ways, with a button to add each turn to the tileset if it isn't in it already
+    fn draw_rotations (&mut self, atlas: &Atlas) {
+        let top = self.big_tile_rect().bottom() + 200.0;
+        let left = LIST_WIDTH + 20.0;
+        draw_text("Rotations:", left, top, FONT_SIZE, WHITE);
+        let mut turned = self.tileset.tiles[self.selected];
+        for turn in 0..4 {
+            let x = left + turn as f32 * 110.0;
+            draw_tile_opt(x, top + 8.0, Vec2::splat(64.0), &turned, atlas);
+            draw_text(format!("{} deg", turn * 90), x, top + 88.0, FONT_SIZE, LIGHTGRAY);
+            let exists = self.tileset.tiles.iter().any(|tileopt| tileopt.connections == turned.connections && tileopt.texture == turned.texture);
+            if !exists && button(Rect::new(x, top + 94.0, 64.0, 24.0), "Add", false) {
+                self.tileset.tiles.push(turned);
+            }
+            turned = rotate_clockwise(&turned);
+        }
+    }
+
+    /// Which connections can connect to which, clicking a cell toggles both it and its mirror
+    fn draw_connection_matrix (&mut self) {
+        const CELL: f32 = 20.0;
+        let count = Connection::ALL.len() as f32;
+        let left = screen_width() - 8.0 - CELL * count;
+        let label_left = left - 100.0;
+        let top = 60.0;
+        draw_text("Can connect:", label_left, top - 8.0, FONT_SIZE, WHITE);
+        for (i, &con1) in Connection::ALL.iter().enumerate() {
+            let y = top + 20.0 + i as f32 * CELL;
+            draw_text(format!("{} {}", i, connection_name(con1)), label_left, y + 14.0, 14.0, WHITE);
+            draw_text(format!("{}", i), left + i as f32 * CELL + 5.0, top + 14.0, 14.0, WHITE);
+            for (j, &con2) in Connection::ALL.iter().enumerate() {
+                let cell = Rect::new(left + j as f32 * CELL, y, CELL, CELL);
+                let connects = self.tileset.can_connect(con1, con2);
+                draw_rectangle(cell.x, cell.y, cell.w, cell.h, if connects {GREEN} else {DARKGRAY});
+                draw_rectangle_lines(cell.x, cell.y, cell.w, cell.h, 1.0, BLACK);
+                if cell.contains(Vec2::from(mouse_position())) && is_mouse_button_pressed(MouseButton::Left) {
+                    self.tileset.set_can_connect(con1, con2, !connects);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt;
+
+/// Everything that can go wrong while generating or drawing a grid
+#[derive(Debug, Clone, PartialEq)]
+pub enum WfcError {
+    /// A tile ran out of options, so the rules can't be satisfied from here
+    Contradiction {x: i32, y: i32},
+    /// An image couldn't be loaded
+    MissingTexture {path: String},
+    /// A tile's options had weights that can't be picked from, like all zero or negative
+    InvalidWeights {weights: Vec<i32>},
+    /// A grid can't be this size
+    InvalidDimensions {width: i32, height: i32},
+    /// A tile was asked for outside the grid
+    OutOfBounds {x: i32, y: i32, width: i32, height: i32},
+}
+
+impl fmt::Display for WfcError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            WfcError::Contradiction {x, y} => write!(f, "No possible tiles left at ({}, {}), the rules are likely too restrictive", x, y),
+            WfcError::MissingTexture {path} => write!(f, "Couldn't load the texture {}", path),
+            WfcError::InvalidWeights {weights} => write!(f, "Can't pick an option with the weights {:?}", weights),
+            WfcError::InvalidDimensions {width, height} => write!(f, "A grid can't be {}x{}", width, height),
+            WfcError::OutOfBounds {x, y, width, height} => write!(f, "({}, {}) is outside the {}x{} grid", x, y, width, height),
+        };
+    }
+}
+
+impl std::error::Error for WfcError {}
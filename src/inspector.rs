@@ -0,0 +1,149 @@
+use crate::*;
+use crate::camera::Camera;
+
+/// The names of the four sides, in the same order as TileChoice::connections
+pub const DIRECTION_NAMES: [&str; 4] = ["up", "right", "down", "left"];
+/// Checking every edge on screen gets slow when zoomed far out, so only this many tiles are checked
+const MAX_CHECKED_TILES: i32 = 4096;
+
+/// The tile next to (x, y) in a direction
+pub fn neighbour (x: i32, y: i32, direction: usize) -> (i32, i32) {
+    return match direction {
+        0 => (x, y-1),
+        1 => (x+1, y),
+        2 => (x, y+1),
+        _ => (x-1, y),
+    };
+}
+
+/// The direction pointing back the other way
+pub fn opposite (direction: usize) -> usize {
+    return (direction + 2) % 4;
+}
+
+/// Every socket the tile could still have on a side
+pub fn side_sockets (tile: &UndecidedTile, direction: usize) -> Vec<Connection> {
+    let mut sockets = Vec::new();
+    for tileopt in tile.possible_tiles.iter() {
+        if !sockets.contains(&tileopt.connections[direction]) {
+            sockets.push(tileopt.connections[direction]);
+        }
+    }
+    return sockets;
+}
+
+/// Whether any socket on one side of a tile can connect to any socket on the facing side of its neighbour.
+/// Edges of the grid are always compatible
+pub fn edge_compatible (grid: &TileGrid, x: i32, y: i32, direction: usize) -> bool {
+    let (nx, ny) = neighbour(x, y, direction);
+    if nx < 0 || nx >= grid.width || ny < 0 || ny >= grid.height {
+        return true;
+    }
+    let ours = side_sockets(&grid.tilegrid[x as usize][y as usize], direction);
+    let theirs = side_sockets(&grid.tilegrid[nx as usize][ny as usize], opposite(direction));
+    let tileset = tileset::current();
+    return ours.iter().any(|&a| theirs.iter().any(|&b| tileset.can_connect(a, b)));
+}
+
+/// Every option a blank tile has that this tile doesn't, along with the directions whose neighbours rule it out now.
+/// An option with no directions wasn't removed by its neighbours, so the tile was collapsed or set to something else
+pub fn eliminated_options (grid: &TileGrid, x: i32, y: i32) -> Vec<(TileChoice, Vec<usize>)> {
+    let tile = &grid.tilegrid[x as usize][y as usize];
+    let tileset = tileset::current();
+    let mut eliminated = Vec::new();
+    for tileopt in tileset.tiles.iter().copied() {
+        if tile.possible_tiles.contains(&tileopt) {
+            continue;
+        }
+        let mut directions = Vec::new();
+        for direction in 0..4 {
+            let (nx, ny) = neighbour(x, y, direction);
+            if nx < 0 || nx >= grid.width || ny < 0 || ny >= grid.height {
+                continue;
+            }
+            let theirs = side_sockets(&grid.tilegrid[nx as usize][ny as usize], opposite(direction));
+            if !theirs.iter().any(|&b| tileset.can_connect(tileopt.connections[direction], b)) {
+                directions.push(direction);
+            }
+        }
+        eliminated.push((tileopt, directions));
+    }
+    return eliminated;
+}
+
+/// A name for an option that tells apart the reoriented versions of the same texture
+pub fn option_name (tileopt: &TileChoice) -> String {
+    let mut name = tileopt.texture.to_string();
+    if tileopt.flipx {name += " flipx";}
+    if tileopt.flipy {name += " flipy";}
+    if tileopt.rot90 {name += " rot90";}
+    return name;
+}
+
+fn socket_names (sockets: &[Connection]) -> String {
+    return sockets.iter().map(|socket| format!("{:?}", socket)).collect::<Vec<String>>().join("/");
+}
+
+/// Outlines incompatible edges on screen in red, labels each side of the selected tile with its sockets,
+/// and lists the options the selected tile has lost along with which neighbours removed them
+pub fn draw_inspector (grid: &TileGrid, camera: &Camera, x: i32, y: i32) {
+    let tile_pixels = camera.tile_pixels();
+
+    // Incompatible edges, only the right and bottom of each tile so every edge is checked once
+    let view = camera.visible_rect();
+    let left = max(0, view.x.floor() as i32);
+    let top = max(0, view.y.floor() as i32);
+    let right = min(grid.width, view.right().ceil() as i32);
+    let bottom = min(grid.height, view.bottom().ceil() as i32);
+    if (right - left) * (bottom - top) <= MAX_CHECKED_TILES {
+        for i in left..right {
+            for j in top..bottom {
+                let corner = camera.world_to_screen(Vec2::new(i as f32, j as f32));
+                if !edge_compatible(grid, i, j, 1) {
+                    draw_line(corner.x + tile_pixels, corner.y, corner.x + tile_pixels, corner.y + tile_pixels, 3.0, RED);
+                }
+                if !edge_compatible(grid, i, j, 2) {
+                    draw_line(corner.x, corner.y + tile_pixels, corner.x + tile_pixels, corner.y + tile_pixels, 3.0, RED);
+                }
+            }
+        }
+    }
+
+    // Socket names around the selected tile
+    const FONT_SIZE: f32 = 16.0;
+    let tile = &grid.tilegrid[x as usize][y as usize];
+    let corner = camera.world_to_screen(Vec2::new(x as f32, y as f32));
+    let centre = corner + Vec2::splat(tile_pixels / 2.0);
+    for direction in 0..4 {
+        let label = socket_names(&side_sockets(tile, direction));
+        let size = measure_text(&label, None, FONT_SIZE as u16, 1.0);
+        let (label_x, label_y) = match direction {
+            0 => (centre.x - size.width / 2.0, corner.y - 4.0),
+            1 => (corner.x + tile_pixels + 4.0, centre.y + size.height / 2.0),
+            2 => (centre.x - size.width / 2.0, corner.y + tile_pixels + size.height + 4.0),
+            _ => (corner.x - size.width - 4.0, centre.y + size.height / 2.0),
+        };
+        let color = if edge_compatible(grid, x, y, direction) {WHITE} else {RED};
+        draw_rectangle(label_x - 2.0, label_y - size.height - 2.0, size.width + 4.0, size.height + 4.0, Color::new(0.0, 0.0, 0.0, 0.8));
+        draw_text(&label, label_x, label_y, FONT_SIZE, color);
+    }
+
+    // Why options were eliminated
+    let mut lines = vec![format!("Eliminated at ({}, {}):", x, y)];
+    for (tileopt, directions) in eliminated_options(grid, x, y) {
+        let cause = if directions.is_empty() {"collapsed".to_string()}
+        else {directions.iter().map(|&direction| DIRECTION_NAMES[direction]).collect::<Vec<&str>>().join(", ")};
+        lines.push(format!("{}: {}", option_name(&tileopt), cause));
+    }
+    let max_lines = max(2, ((screen_height() - 120.0) / FONT_SIZE) as usize);
+    if lines.len() > max_lines {
+        let hidden = lines.len() - max_lines + 1;
+        lines.truncate(max_lines - 1);
+        lines.push(format!("... and {} more", hidden));
+    }
+    let width = lines.iter().map(|line| measure_text(line, None, FONT_SIZE as u16, 1.0).width).fold(0.0, f32::max) + 10.0;
+    draw_rectangle(0.0, 24.0, width, lines.len() as f32 * FONT_SIZE + 8.0, Color::new(0.0, 0.0, 0.0, 0.8));
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(line, 5.0, 24.0 + (i + 1) as f32 * FONT_SIZE, FONT_SIZE, WHITE);
+    }
+}
@@ -2,17 +2,60 @@
 
 use macroquad::prelude::*;
 use ::rand::seq::SliceRandom;
-use std::collections::HashMap;
+use ::rand::rngs::StdRng;
+use ::rand::SeedableRng;
+use ::rand::Rng;
+use std::collections::HashSet;
 
 mod renderers;
-use renderers::whitegrid::draw_tilegrid as draw_whitegrid;
-use renderers::debug_grid_draw::draw_tilegrid as debug_draw_tilegrid;
-use renderers::texture::load_textures_paths;
-use renderers::texture::draw_tilegrid;
+use renderers::Renderer;
+use renderers::whitegrid::WhitegridRenderer;
+use renderers::debug_grid_draw::DebugRenderer;
+use renderers::texture::TextureRenderer;
+use renderers::superposition::SuperpositionRenderer;
+use renderers::autotile::AutotileRenderer;
+use renderers::heatmap::{HeatmapRenderer, HeatmapMode};
 use renderers::texture::draw_tile_opt;
+use renderers::manifest::{TextureManifest, TextureWatcher};
+use renderers::chunks::ChunkCache;
+use renderers::batch::set_drawcall_capacity;
+#[cfg(feature = "colored_wires")]
+use renderers::wires::WiresRenderer;
+#[cfg(feature = "colored_wires")]
+use renderers::triangles::TrianglesRenderer;
 
 mod wavefunctions;
+#[cfg(feature = "castle")]
 use wavefunctions::castle::*;
+#[cfg(feature = "terrain")]
+use wavefunctions::terrain::*;
+#[cfg(feature = "islands")]
+use wavefunctions::islands::*;
+#[cfg(feature = "puzzle")]
+use wavefunctions::puzzle::*;
+#[cfg(feature = "colored_wires")]
+use wavefunctions::colored_wires::*;
+#[cfg(not(any(feature = "castle", feature = "terrain", feature = "islands", feature = "puzzle", feature = "colored_wires")))]
+compile_error!("Build with one of the wavefunction features, like --features castle");
+
+mod parallel;
+mod solver;
+use solver::{Solver, Failure};
+mod camera;
+use camera::Camera;
+mod inspector;
+mod trace;
+mod tileset;
+mod editor;
+use editor::Editor;
+mod browser;
+use browser::Browser;
+mod validate;
+mod big_tiles;
+mod error;
+use error::WfcError;
+use tileset::Tileset;
+use trace::{Trace, TraceEvent, Replay, TRACE_PATH};
 
 use std::cmp::max;
 use std::cmp::min;
@@ -21,8 +64,26 @@ use std::cmp::min;
 struct UndecidedTile {
     possible_tiles: Vec<TileChoice>,
 }
+
+impl UndecidedTile {
+    /// The Shannon entropy of the tile's options, weighted by their weights. 0 once the tile is decided
+    fn entropy (&self) -> f32 {
+        let weights = self.possible_tiles.iter().map(|tileopt| max(tileopt.weight, 0) as f32);
+        let total: f32 = weights.clone().sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        // H = ln(W) - sum(w ln w) / W
+        let weighted_logs: f32 = weights.filter(|&w| w > 0.0).map(|w| w * w.ln()).sum();
+        return total.ln() - weighted_logs / total;
+    }
+}
+/// Tiles that have been changed, with the options they had before, in the order they were changed
+type Changes = Vec<(i32, i32, UndecidedTile)>;
+
 #[derive(Clone, Debug)]
-/// Contains a 2d grid of tiles, functions relating to manipulating that grid, and functions and data relating to rendering that grid
+/// Contains a 2d grid of tiles and functions relating to manipulating that grid
+/// Rendering state lives in main so that the grid can be sent to other threads to be generated
 struct TileGrid {
     /// The grid of tiles
     tilegrid: Vec<Vec<UndecidedTile>>,
@@ -30,51 +91,56 @@ struct TileGrid {
     width: i32,
     /// The height of the grid in tiles
     height: i32,
-    ///the texture which the tilegrid is rendered to
-    tilegrid_texture: RenderTarget,
-    /// The number of pixels wide each tile is
-    lod_x: i32,
-    /// The number of pixels tall each tile is
-    lod_y: i32,
-    /// whether or not to render a white grid over the tilegrid
-    whitegrid: bool,
-    /// Whether to render the tilegrid as a texture or as debug information
-    rendermode: Rendermode,
-    /// A hashmap of textures to use for rendering the tilegrid
-    texturemap: HashMap<&'static str, Texture2D>,
+    /// The tiles whose options have changed since the last call to take_dirty
+    dirty: HashSet<(i32, i32)>,
+    /// Decides which tile pick_index chooses and which option each collapse picks. It belongs to the grid so a copy of it can say
+    /// which tile will be picked next, and so giving a grid a new one makes it collapse differently
+    rng: StdRng,
+    /// Every change made to the grid while a trace is being recorded
+    trace: Option<Trace>,
+    /// While a change is being made atomically, every tile it has changed so far, so it can be rolled back if it fails
+    changes: Option<Changes>,
+    /// Picks which variant each tile's texture is drawn with, so tiles keep their look until the grid is reset
+    variant_seed: u64,
+    /// The tileset's layers stacked on top of the grid, bottom first, which are generated once every tile is decided.
+    /// Empty until then, and layers never have layers of their own
+    layers: Vec<TileGrid>,
 }
 
 /// Functions relating to the tiles and tilegrid
 impl TileGrid {
-    fn new (width: i32, height: i32, texturemap: HashMap<&'static str, Texture2D>) -> Self {
-        let mut tilegrid = Vec::<Vec<UndecidedTile>>::new();
-        for _ in 0..width {
-            let mut col = Vec::<UndecidedTile>::new();
-            for _ in 0..height {
-                col.push(UndecidedTile::new());
-            }
-            tilegrid.push(col);
+    fn new (width: i32, height: i32) -> Result<Self, WfcError> {
+        if width < 1 || height < 1 {
+            return Err(WfcError::InvalidDimensions {width, height});
         }
-        let mut output = Self {
-            tilegrid,
+        let mut output = Self::blank(width, height);
+        output.restrict_grid()?;
+        return Ok(output);
+    }
+
+    fn blank (width: i32, height: i32) -> Self { // A grid where every tile has every option, without restricting the edges like new does
+        let mut rng = StdRng::from_entropy();
+        let variant_seed = rng.gen();
+        let mut grid = Self {
+            tilegrid: vec![vec![UndecidedTile::new(); height as usize]; width as usize],
             width,
             height,
-            tilegrid_texture: render_target(10, 10),
-            lod_x: 1,
-            lod_y: 1,
-            whitegrid: false,
-            rendermode: Rendermode::Texture,
-            texturemap,
+            dirty: HashSet::new(),
+            rng,
+            trace: None,
+            changes: None,
+            variant_seed,
+            layers: Vec::new(),
         };
-        output.restrict_grid();
-        return output;
+        grid.mark_all_dirty();
+        return grid;
     }
 
-    fn expand_to (&mut self, width: i32, height: i32) {
+    fn expand_to (&mut self, width: i32, height: i32) -> Result<(), WfcError> { // Leaves the grid the size it was if the new tiles can't fit
         let old_height = self.height;
         let old_width = self.width;
         if width < 1 || height < 1 {
-            return;
+            return Err(WfcError::InvalidDimensions {width, height});
         }
         while self.height < height {
             for col in self.tilegrid.iter_mut() {
@@ -100,6 +166,9 @@ impl TileGrid {
             }
             self.height = height;
         }
+        // The layers were generated for the old size
+        self.layers.clear();
+        self.record_snapshot();
         let mut to_propegate = Vec::<(i32, i32)>::new();
         for i in 0..old_width {
             for j in old_height..self.height {
@@ -111,10 +180,16 @@ impl TileGrid {
                 to_propegate.push((i, j));
             }
         }
-        self.restrict_and_propegate(to_propegate);
+        let tileset = tileset::current();
+        if let Err(error) = self.atomically(|grid| grid.restrict_and_propegate(&tileset, to_propegate)) {
+            // Shrinking back never restricts anything, so it can't fail
+            self.expand_to(old_width, old_height)?;
+            return Err(error);
+        }
+        return Ok(());
     }
 
-    fn shift (&mut self, x: i32, y: i32) {
+    fn shift (&mut self, x: i32, y: i32) -> Result<(), WfcError> { // Puts the tiles back where they were if the new tiles can't fit
         let mut new_tilegrid = Vec::<Vec<UndecidedTile>>::new();
         for i in 0..self.width {
             let mut col = Vec::<UndecidedTile>::new();
@@ -130,20 +205,31 @@ impl TileGrid {
             }
             new_tilegrid.push(col);
         }
-        self.tilegrid = new_tilegrid;
+        let old_tilegrid = std::mem::replace(&mut self.tilegrid, new_tilegrid);
+        self.layers.clear();
+        self.mark_all_dirty();
+        self.record_snapshot();
         let top = max(0, -y);
         let bottom = min(self.height, self.height-y);
         let left = max(0, -x);
         let right = min(self.width, self.width-x);
-        self.restrict_grid_edges(top, bottom, left, right);
+        if let Err(error) = self.restrict_grid_edges(top, bottom, left, right) {
+            self.tilegrid = old_tilegrid;
+            self.mark_all_dirty();
+            self.record_snapshot();
+            return Err(error);
+        }
+        return Ok(());
     }
 
-    fn restrict_tile (&mut self, x: i32, y: i32) -> bool { // returns true if a change was made
+    fn restrict_tile (&mut self, tileset: &Tileset, x: i32, y: i32) -> Result<bool, WfcError> { // returns true if a change was made, leaving the tile alone if it would have no options left
         if x < 0 || x >= self.width || y < 0 || y >= self.height {
-            return false;
+            return Ok(false);
         }
         let mut did_something = false;
         let mut tile = self.tilegrid[x as usize][y as usize].clone();
+        // The options removed because of the neighbour in each direction, for the trace
+        let mut removed_by: [Vec<TileChoice>; 4] = Default::default();
         for connection_direction in 0..4 {
             let neighbor_indices = match connection_direction {
                 0 => (x,y-1),
@@ -157,11 +243,13 @@ impl TileGrid {
                 2 => 0,
                 _ => 1,
             };
-            if neighbor_indices.0 < 0 || neighbor_indices.0 >= self.width || neighbor_indices.1 < 0 || neighbor_indices.1 >= self.height {
+            let in_grid = neighbor_indices.0 >= 0 && neighbor_indices.0 < self.width && neighbor_indices.1 >= 0 && neighbor_indices.1 < self.height;
+            // Big tiles can't hang off the edge of the grid, but nothing else is restricted by it
+            if !in_grid && !tileset.has_big_tiles() {
                 continue;
             }
 
-            let neighbor_tile = self.tilegrid[neighbor_indices.0 as usize][neighbor_indices.1 as usize].clone();
+            let neighbor_tile = if in_grid {self.tilegrid[neighbor_indices.0 as usize][neighbor_indices.1 as usize].clone()} else {UndecidedTile {possible_tiles: Vec::new()}};
             let mut possible_connections = Vec::<Connection>::new();
             for tile_option in neighbor_tile.possible_tiles.iter() {
                 let connection = tile_option.connections[neighbor_connection_direction];
@@ -171,32 +259,46 @@ impl TileGrid {
             }
 
             for tile_option_index in (0..tile.possible_tiles.len()).rev() {
-                let self_connection = 
-                    tile.possible_tiles[tile_option_index].connections[connection_direction];
+                let tile_option = &tile.possible_tiles[tile_option_index];
+                let self_connection = tile_option.connections[connection_direction];
 
                 let mut can_connect = false;
-                for connection in possible_connections.iter() {
-                    if Connection::can_connect(*connection, self_connection) {
-                        can_connect = true;
+                if !in_grid {
+                    can_connect = !tileset.faces_inside(tile_option, connection_direction);
+                }
+                else if tileset.has_big_tiles() {
+                    // Parts of big tiles depend on more than the connection, so every pair of options has to be checked
+                    can_connect = neighbor_tile.possible_tiles.iter().any(|neighbor_option| tileset.allows(neighbor_option, tile_option, neighbor_connection_direction));
+                }
+                else {
+                    for connection in possible_connections.iter() {
+                        if tileset.can_connect(*connection, self_connection) {
+                            can_connect = true;
+                        }
                     }
                 }
                 if !can_connect {
-                    tile.possible_tiles.remove(tile_option_index);
-                    assert!(!tile.possible_tiles.is_empty(), "No possible tiles left at ({}, {}), Rules are likely too restrictive. Please try again.", x, y);
+                    removed_by[connection_direction].push(tile.possible_tiles.remove(tile_option_index));
+                    if tile.possible_tiles.is_empty() {
+                        self.record_reductions(x, y, removed_by);
+                        self.record(TraceEvent::Contradiction {x, y});
+                        return Err(WfcError::Contradiction {x, y});
+                    }
                     did_something = true;
                 }
             }
         }
         if did_something {
-            self.tilegrid[x as usize][y as usize] = tile;
+            self.replace_tile(tileset, x, y, tile);
+            self.record_reductions(x, y, removed_by);
         }
-        return did_something;
+        return Ok(did_something);
     }
 
-    fn restrict_and_propegate (&mut self, vec: Vec<(i32, i32)>) { // Will restrict all tile indexes in the vec and propegate changes
+    fn restrict_and_propegate (&mut self, tileset: &Tileset, vec: Vec<(i32, i32)>) -> Result<(), WfcError> { // Will restrict all tile indexes in the vec against the tileset and propegate changes, stopping at the first contradiction
         let mut todo_indices = vec.clone();
         while let Some((x, y)) = todo_indices.pop() {
-            if self.restrict_tile(x, y) {
+            if self.restrict_tile(tileset, x, y)? {
                 for i in 0..4 {
                     let neighbor_indices = match i {
                         0 => (x,y-1),
@@ -210,26 +312,93 @@ impl TileGrid {
                 }
             }
         }
+        return Ok(());
     }
 
-    fn collapse_and_propegate (&mut self, x: i32, y: i32) { // Will collapse the tile at the index and propegate changes
+    fn contains (&self, x: i32, y: i32) -> bool { // Whether the index is inside the grid
+        return x >= 0 && x < self.width && y >= 0 && y < self.height;
+    }
 
-        self.tilegrid[x as usize][y as usize].collapse();
-        let todo_indices = vec![(x, y), (x, y-1), (x, y+1), (x-1, y), (x+1, y)];
-        self.restrict_and_propegate(todo_indices);
+    fn collapse_and_propegate (&mut self, x: i32, y: i32) -> Result<(), WfcError> { // Will collapse the tile at the index and propegate changes, leaving the grid as it was if that fails
+        // Edits are made after the viewer picked the tile, so the grid might have shrunk since
+        if !self.contains(x, y) {
+            return Ok(());
+        }
+        let chosen = self.tilegrid[x as usize][y as usize].choose(&mut self.rng)?;
+        self.collapse_to(x, y, chosen)?;
+        return Ok(());
     }
 
-    fn restrict_grid (&mut self) { // By the end of the function, there will be no cases where a tile has an invalid possibility
+    fn collapse_to (&mut self, x: i32, y: i32, chosen: TileChoice) -> Result<Changes, WfcError> { // Collapses the tile to one option and propegates, returning every tile changed so it can be undone later. Collapsing part of a big tile places all of it
+        let tileset = tileset::current();
+        return self.atomically(|grid| {
+            let mut todo_indices = Vec::<(i32, i32)>::new();
+            for (cx, cy, part) in tileset.big_tile_cells(x, y, chosen) {
+                if cx < 0 || cx >= grid.width || cy < 0 || cy >= grid.height || !grid.tilegrid[cx as usize][cy as usize].possible_tiles.contains(&part) {
+                    return Err(WfcError::Contradiction {x: cx, y: cy});
+                }
+                if grid.tilegrid[cx as usize][cy as usize].possible_tiles.len() > 1 {
+                    grid.record(TraceEvent::Collapse {x: cx, y: cy, chosen: part});
+                }
+                grid.replace_tile(&tileset, cx, cy, UndecidedTile {possible_tiles: vec![part]});
+                todo_indices.extend([(cx, cy), (cx, cy-1), (cx, cy+1), (cx-1, cy), (cx+1, cy)]);
+            }
+            return grid.restrict_and_propegate(&tileset, todo_indices);
+        });
+    }
+
+    fn forbid (&mut self, x: i32, y: i32, option: TileChoice) -> Result<Changes, WfcError> { // Removes one option from a tile and propegates, for backtracking out of a collapse that went wrong
+        let tileset = tileset::current();
+        return self.atomically(|grid| {
+            let mut tile = grid.tilegrid[x as usize][y as usize].clone();
+            tile.possible_tiles.retain(|tileopt| *tileopt != option);
+            if tile.possible_tiles.is_empty() {
+                return Err(WfcError::Contradiction {x, y});
+            }
+            grid.set_tile(&tileset, x, y, tile);
+            let todo_indices = vec![(x, y-1), (x, y+1), (x-1, y), (x+1, y)];
+            return grid.restrict_and_propegate(&tileset, todo_indices);
+        });
+    }
+
+    fn atomically (&mut self, change: impl FnOnce(&mut Self) -> Result<(), WfcError>) -> Result<Changes, WfcError> { // Makes a change which either works completely or leaves the grid as it was, returning the tiles it changed
+        // A change made inside another atomic change is rolled back along with it
+        let outer = self.changes.replace(Vec::new());
+        let result = change(self);
+        let changes = self.changes.take().unwrap_or_default();
+        self.changes = outer;
+        match result {
+            Ok(()) => {
+                if let Some(outer) = &mut self.changes {
+                    outer.extend(changes.iter().cloned());
+                }
+                return Ok(changes);
+            }
+            Err(error) => {
+                self.roll_back(changes);
+                return Err(error);
+            }
+        }
+    }
+
+    fn roll_back (&mut self, changes: Changes) { // Puts back the tiles from before a change, newest first
+        let tileset = tileset::current();
+        for (x, y, tile) in changes.into_iter().rev() {
+            self.set_tile(&tileset, x, y, tile);
+        }
+    }
+
+    fn restrict_grid (&mut self) -> Result<(), WfcError> { // By the end of the function, there will be no cases where a tile has an invalid possibility
         let mut todo_indices = Vec::<(i32, i32)>::new();
         for i in 0..self.width {
             for j in 0..self.height {
                 todo_indices.push((i, j));
             }
         }    
-        self.restrict_and_propegate(todo_indices);
+        return self.restrict_and_propegate(&tileset::current(), todo_indices);
     }
 
-    fn restrict_grid_edges(&mut self, top:i32, bottom:i32, left:i32, right:i32) { // Restricts the edges of the grid
+    fn restrict_grid_edges(&mut self, top:i32, bottom:i32, left:i32, right:i32) -> Result<(), WfcError> { // Restricts the edges of the grid
         let mut todo_indices = Vec::<(i32, i32)>::new();
         for i in 0..self.width {
             for j in 0..bottom {
@@ -247,18 +416,254 @@ impl TileGrid {
                 todo_indices.push((i, j));
             }
         }
-        self.restrict_and_propegate(todo_indices);
+        return self.restrict_and_propegate(&tileset::current(), todo_indices);
+    }
+
+    fn supported_options (&self, tileset: &Tileset, x: i32, y: i32, relaxed: &HashSet<(i32, i32)>) -> Vec<TileChoice> { // Every option the neighbours of a tile can support, treating relaxed tiles as fully undecided. Uses the same rules as restrict_tile
+        let mut options = tileset.tiles.clone();
+        for connection_direction in 0..4 {
+            let (neighbor_indices, neighbor_connection_direction) = match connection_direction {
+                0 => ((x,y-1), 2),
+                1 => ((x+1,y), 3),
+                2 => ((x,y+1), 0),
+                _ => ((x-1,y), 1),
+            };
+            if !self.contains(neighbor_indices.0, neighbor_indices.1) {
+                // Big tiles can't hang off the edge of the grid, but nothing else is restricted by it
+                options.retain(|option| !tileset.faces_inside(option, connection_direction));
+                continue;
+            }
+            let neighbor_options = if relaxed.contains(&neighbor_indices) {
+                &tileset.tiles
+            }
+            else {
+                &self.tilegrid[neighbor_indices.0 as usize][neighbor_indices.1 as usize].possible_tiles
+            };
+            if tileset.has_big_tiles() {
+                // Parts of big tiles depend on more than the connection, so every pair of options has to be checked
+                options.retain(|option| neighbor_options.iter().any(|neighbor_option| tileset.allows(neighbor_option, option, neighbor_connection_direction)));
+            }
+            else {
+                let connections: HashSet<Connection> = neighbor_options.iter().map(|neighbor_option| neighbor_option.connections[neighbor_connection_direction]).collect();
+                options.retain(|option| connections.iter().any(|connection| tileset.can_connect(*connection, option.connections[connection_direction])));
+            }
+        }
+        return options;
+    }
+
+    fn uncollapse (&mut self, x: i32, y: i32) -> Result<(), WfcError> { // Clears a tile and relaxes only the tiles whose options could have depended on it
+        if !self.contains(x, y) {
+            return Ok(());
+        }
+        let tileset = tileset::current();
+        // A placed big tile is cleared all at once, since its other parts would only allow this part back
+        let mut region = HashSet::from([(x, y)]);
+        if let [placed] = self.tilegrid[x as usize][y as usize].possible_tiles[..] {
+            for (i, j, part) in tileset.big_tile_cells(x, y, placed) {
+                if self.contains(i, j) && self.tilegrid[i as usize][j as usize].possible_tiles == [part] {
+                    region.insert((i, j));
+                }
+            }
+        }
+        // Grow the region outwards for as long as relaxing it would give tiles more options than they have now
+        // Decided tiles are never relaxed, so they act as the edge of the region
+        let mut todo_indices = Vec::<(i32, i32)>::new();
+        let mut queued = HashSet::<(i32, i32)>::new();
+        for &(i, j) in region.iter() {
+            for neighbor_indices in [(i,j-1), (i,j+1), (i-1,j), (i+1,j)] {
+                if queued.insert(neighbor_indices) {
+                    todo_indices.push(neighbor_indices);
+                }
+            }
+        }
+        while let Some((i, j)) = todo_indices.pop() {
+            queued.remove(&(i, j));
+            if !self.contains(i, j) || region.contains(&(i, j)) {
+                continue;
+            }
+            let current_options = self.tilegrid[i as usize][j as usize].possible_tiles.len();
+            if current_options == 1 {
+                continue;
+            }
+            if self.supported_options(&tileset, i, j, &region).len() > current_options {
+                region.insert((i, j));
+                for neighbor_indices in [(i,j-1), (i,j+1), (i-1,j), (i+1,j)] {
+                    if queued.insert(neighbor_indices) {
+                        todo_indices.push(neighbor_indices);
+                    }
+                }
+            }
+        }
+        return self.clear_region(&region.into_iter().collect::<Vec<(i32, i32)>>());
+    }
+
+    fn clear_region (&mut self, region: &[(i32, i32)]) -> Result<(), WfcError> { // Resets the tiles to undecided and restricts them against the tiles bordering them
+        let tileset = tileset::current();
+        return self.atomically(|grid| {
+            for &(x, y) in region.iter() {
+                grid.set_tile(&tileset, x, y, UndecidedTile {possible_tiles: tileset.tiles.clone()});
+            }
+            // The region's new options can only be limited by the tiles bordering it, so only those need restricting
+            let mut todo_indices: HashSet<(i32, i32)> = region.iter().copied().collect();
+            for &(x, y) in region.iter() {
+                todo_indices.extend([(x,y-1), (x,y+1), (x-1,y), (x+1,y)]);
+            }
+            return grid.restrict_and_propegate(&tileset, todo_indices.into_iter().collect());
+        }).map(|_| ());
+    }
+
+    fn reroll_region (&mut self, cells: &[(i32, i32)]) -> Result<(), WfcError> { // Clears the cells back to undecided, re-propegates from the decided tiles around them, then collapses only those cells
+        let mut seen = HashSet::<(i32, i32)>::new();
+        let region: Vec<(i32, i32)> = cells.iter().copied().filter(|&(x, y)| self.contains(x, y) && seen.insert((x, y))).collect();
+        return self.atomically(|grid| {
+            grid.clear_region(&region)?;
+            return grid.collapse_region(&region);
+        }).map(|_| ());
+    }
+
+    fn set_tile (&mut self, tileset: &Tileset, x: i32, y: i32, tile: UndecidedTile) { // Replaces a tile's options without propegating
+        if self.trace.is_some() {
+            self.record(TraceEvent::Set {x, y, options: tile.possible_tiles.clone()});
+        }
+        self.replace_tile(tileset, x, y, tile);
+    }
+
+    fn replace_tile (&mut self, tileset: &Tileset, x: i32, y: i32, tile: UndecidedTile) { // Replaces a tile's options, remembering the old ones if a change is being made atomically
+        let old = std::mem::replace(&mut self.tilegrid[x as usize][y as usize], tile);
+        if let Some(changes) = &mut self.changes {
+            changes.push((x, y, old));
+        }
+        self.dirty.insert((x, y));
+        // Whatever was on top of the tile was picked for what used to be there, so it's taken off until the layers are generated again
+        if !self.layers.is_empty() {
+            for (layer, grid) in self.layers.iter_mut().enumerate() {
+                grid.tilegrid[x as usize][y as usize] = UndecidedTile {possible_tiles: tileset.layers[layer].tiles.clone()};
+            }
+        }
+    }
+
+    fn layer_on (below: &TileGrid, layer: usize) -> Result<Self, WfcError> { // A new grid for one of the tileset's layers, with each tile only allowed the options that can sit on the tile below it
+        let tileset = tileset::current();
+        let mut grid = Self::blank(below.width, below.height);
+        grid.variant_seed = below.variant_seed;
+        for i in 0..grid.width {
+            for j in 0..grid.height {
+                let below_options = &below.tilegrid[i as usize][j as usize].possible_tiles;
+                let options: Vec<TileChoice> = tileset.layers[layer].tiles.iter()
+                    .filter(|tileopt| below_options.iter().any(|below_option| tileset.sits_on(layer, tileopt, below_option)))
+                    .copied().collect();
+                if options.is_empty() {
+                    return Err(WfcError::Contradiction {x: i, y: j});
+                }
+                grid.tilegrid[i as usize][j as usize] = UndecidedTile {possible_tiles: options};
+            }
+        }
+        if !tileset.layers[layer].free {
+            grid.restrict_grid()?;
+        }
+        return Ok(grid);
+    }
+
+    fn generate_layers (&mut self) -> Result<(), WfcError> { // Collapses each of the tileset's layers on top of the one below it, leaving the old layers alone if any of them contradicts
+        let mut layers = Vec::<TileGrid>::new();
+        let tileset = tileset::current();
+        for layer in 0..tileset.layers.len() {
+            let mut grid = Self::layer_on(layers.last().unwrap_or(self), layer)?;
+            if tileset.layers[layer].free {
+                // Free tiles don't restrict each other, so each one is picked on its own without propegating
+                for tile in grid.tilegrid.iter_mut().flatten() {
+                    tile.collapse(&mut grid.rng)?;
+                }
+            }
+            while let Some((x, y)) = solver::next_index(&mut grid) {
+                grid.collapse_and_propegate(x, y)?;
+            }
+            // Only the grid's own tiles are tracked, the layers are drawn again all at once
+            grid.take_dirty();
+            layers.push(grid);
+        }
+        self.layers = layers;
+        return Ok(());
+    }
+
+    fn layers_pending (&self) -> bool { // Whether the layers need generating, because they haven't been or some of their tiles have been taken off since
+        return self.layers.len() != tileset::current().layers.len()
+            || self.layers.iter().any(|grid| grid.tilegrid.iter().flatten().any(|tile| tile.possible_tiles.len() != 1));
+    }
+
+
+    fn reset (&mut self) -> Result<(), WfcError> { // Replaces every tile with a blank one, carrying on any trace being recorded
+        let mut grid = TileGrid::new(self.width, self.height)?;
+        grid.trace = self.trace.take();
+        *self = grid;
+        self.record_snapshot();
+        return Ok(());
+    }
+
+    fn start_trace (&mut self) {
+        self.trace = Some(Trace::start(self));
+    }
+
+    fn stop_trace (&mut self) { // Saves the trace and stops recording
+        self.save_trace();
+        self.trace = None;
+    }
+
+    fn save_trace (&self) {
+        if let Some(trace) = &self.trace {
+            match trace.save(TRACE_PATH) {
+                Ok(()) => println!("Saved a trace of {} events to {}", trace.events.len(), TRACE_PATH),
+                Err(error) => println!("Failed to save trace to {}: {}", TRACE_PATH, error),
+            }
+        }
+    }
+
+    fn record (&mut self, event: TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.events.push(event);
+        }
+    }
+
+    fn record_reductions (&mut self, x: i32, y: i32, removed_by: [Vec<TileChoice>; 4]) {
+        for (direction, removed) in removed_by.into_iter().enumerate() {
+            if !removed.is_empty() {
+                self.record(TraceEvent::Reduce {x, y, direction, removed});
+            }
+        }
+    }
+
+    fn record_snapshot (&mut self) { // Records the whole grid, after it's been changed in a way that isn't tile by tile
+        if let Some(mut trace) = self.trace.take() {
+            trace.snapshot(self);
+            self.trace = Some(trace);
+        }
     }
 
-    fn unrestrict_grid (&mut self) { // Will remove all restrictions unless a tile has only one possible tile
+    fn mark_all_dirty (&mut self) {
         for i in 0..self.width {
             for j in 0..self.height {
-                if self.tilegrid[i as usize][j as usize].possible_tiles.len() > 1 {
-                    self.tilegrid[i as usize][j as usize] = UndecidedTile::new();
-                }
+                self.dirty.insert((i, j));
             }
         }
-        self.restrict_grid();
+    }
+
+    fn take_dirty (&mut self) -> HashSet<(i32, i32)> { // Returns every tile changed since the last call, and starts tracking again
+        return std::mem::take(&mut self.dirty);
+    }
+
+    fn collapse_region (&mut self, region: &[(i32, i32)]) -> Result<(), WfcError> { // Collapses every tile in the region, leaving tiles outside it undecided unless propegation decides them
+        return self.atomically(|grid| {
+            // Collapse the most restricted tile in the region first, the same way pick_index prefers low option counts
+            loop {
+                let next = region.iter()
+                    .filter(|(x, y)| grid.tilegrid[*x as usize][*y as usize].possible_tiles.len() > 1)
+                    .min_by_key(|(x, y)| grid.tilegrid[*x as usize][*y as usize].possible_tiles.len());
+                match next {
+                    Some(&(x, y)) => grid.collapse_and_propegate(x, y)?,
+                    None => return Ok(()),
+                }
+            }
+        }).map(|_| ());
     }
 
 }
@@ -272,112 +677,187 @@ impl TileGrid {
 enum Rendermode {
     Texture,
     Debug,
+    Superposition,
+    Autotile,
+    #[cfg(feature = "colored_wires")]
+    Wires,
+    #[cfg(feature = "colored_wires")]
+    Triangles,
 }
 
-#[macroquad::main("WavefunctionCollapse")]
-async fn main() {              
-
-    let mut framecount = 0;
-    let texturemap = load_textures_paths(
-        &[
-            "assets/castle/Door L.png",
-            "assets/castle/Door R.png",
-            "assets/castle/Door TL.png",
-            "assets/castle/Door TR.png",
-            "assets/castle/Wall 1.png",
-            "assets/castle/Wall 2.png",
-            "assets/castle/Wall L1.png",
-            "assets/castle/Wall L2.png",
-            "assets/castle/Wall LC.png",
-            "assets/castle/Wall R1.png",
-            "assets/castle/Wall R2.png",
-            "assets/castle/Wall RC.png",
-            "assets/castle/Wall T.png",
-            "assets/castle/Wall TL.png",
-            "assets/castle/Wall TR.png",
-            "assets/castle/Window 1.png",
-            "assets/castle/Window 2.png",
-            "assets/castle/Sky.png",
-        ],
-        &[
-            "Door L",
-            "Door R",
-            "Door TL",
-            "Door TR",
-            "Wall 1",
-            "Wall 2",
-            "Wall L1",
-            "Wall L2",
-            "Wall LC",
-            "Wall R1",
-            "Wall R2",
-            "Wall RC",
-            "Wall T",
-            "Wall TL",
-            "Wall TR",
-            "Window 1",
-            "Window 2",
-            "Sky",
-        ]
-    ).await;
-    let mut grid = TileGrid::new(10, 10, texturemap.clone());
-    let mut zoom_x = 1.0;
-    let mut zoom_y = 1.0;
-    const MARGIN_X: f32 = 10.0;
-    const MARGIN_Y: f32 = 10.0;
-    // Holds a texture that is used when zooming out, to fill up the area that the tilegrid texture doesn't cover before it gets rendered
-    // 2x2 pixels
-    let placeholders = [
-        render_target(2048, 2048),//2x2
-        render_target(2048, 2048),//4x4
-        render_target(2048, 2048),//8x8
-        render_target(2048, 2048),//16x16
-        render_target(2048, 2048),//32x32
-    ];
-    for placeholder in placeholders.iter() {
-        placeholder.texture.set_filter(FilterMode::Nearest);
+/// Shows an error in the middle of the screen, with what can be done about it underneath
+fn draw_error (error: &WfcError, options: &str) {
+    const FONT_SIZE: f32 = 20.0;
+    let message = error.to_string();
+    let width = measure_text(&message, None, FONT_SIZE as u16, 1.0).width.max(measure_text(options, None, FONT_SIZE as u16, 1.0).width) + 20.0;
+    let (x, y) = ((screen_width() - width) / 2.0, screen_height() / 2.0 - 30.0);
+    draw_rectangle(x, y, width, 60.0, Color::new(0.0, 0.0, 0.0, 0.85));
+    draw_rectangle_lines(x, y, width, 60.0, 2.0, RED);
+    draw_text(&message, x + 10.0, y + 24.0, FONT_SIZE, RED);
+    draw_text(options, x + 10.0, y + 48.0, FONT_SIZE, WHITE);
+}
+
+fn main () {
+    // `validate` checks the tileset and exits instead of opening the window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("validate") {
+        std::process::exit(validate::run(&args[1..]));
     }
+    macroquad::Window::new("WavefunctionCollapse", run());
+}
+
+async fn run () {
+
+    set_drawcall_capacity();
+    match Tileset::load(TILESET_PATH) {
+        Ok(tileset) => tileset::set_current(tileset),
+        Err(error) => println!("Using the default tileset. {}", error),
+    }
+
+    // Only the first missing texture is shown, the rest are printed by load_atlas_paths
+    let mut texture_error = None;
+    let mut manifest = TextureManifest::load(TEXTURE_FOLDER).unwrap_or_else(|error| {
+        texture_error = Some(error);
+        TextureManifest {folder: TEXTURE_FOLDER.into(), entries: Vec::new(), sheets: Vec::new()}
+    });
+    let (mut atlas, texture_errors) = manifest.load_atlas().await;
+    texture_error = texture_error.or(texture_errors.into_iter().next());
+    let mut texture_watcher = TextureWatcher::new(&manifest);
+    let mut texture_renderer = TextureRenderer {atlas: atlas.clone()};
+    let mut superposition_renderer = SuperpositionRenderer::new(atlas.clone());
+    let mut autotile_renderer = AutotileRenderer::new(atlas.clone());
+    let (mut grid, grid_error) = match TileGrid::new(10, 10) {
+        Ok(grid) => (grid, None),
+        Err(error) => (TileGrid::blank(10, 10), Some(error)),
+    };
+    // The tilegrid rendered in chunks, which are drawn to the screen each frame
+    let mut chunk_cache = ChunkCache::new();
+    // The heatmap overlay, if it's on, and its own chunks
+    let mut heatmap: Option<HeatmapRenderer> = None;
+    let mut heatmap_cache = ChunkCache::new();
+    // Whether or not to render a white grid over the tilegrid
+    let mut whitegrid = false;
+    // Whether to show the sockets around the hovered tile and why its options were removed
+    let mut inspector = false;
+    // Whether the solver is recording a trace, and the trace being replayed if there is one
+    let mut recording = false;
+    let mut replay: Option<Replay> = None;
+    // The tileset editor, which takes over the whole screen while it's open
+    let mut editor = Editor::new();
+    // Shows which tiles can sit next to which, also over the whole screen
+    let mut browser = Browser::new();
+    // Whether to render the tilegrid as a texture or as debug information
+    let mut rendermode = Rendermode::Texture;
+    let mut camera = Camera::new();
+    // Cells selected by the reroll brush, and the corner the rectangle brush was started from
+    let mut brush_cells = Vec::<(i32, i32)>::new();
+    let mut brush_anchor: Option<(i32, i32)> = None;
+    // Owns the real grid and collapses it on another thread, grid is kept as a copy of it for rendering
+    let mut solver = Solver::start(grid.clone());
+    solver.failure = grid_error.map(|error| Failure {error, can_backtrack: false});
 
     loop {
-        // Zoom
-        {
-            if is_key_down(KeyCode::Equal) && zoom_x < 30.0 && zoom_y < 30.0 {
-                zoom_x *= 1.01;
-                zoom_y *= 1.01;
+        // Changes made by the solver thread since last frame, which wait until the replay is over so they don't mix with it
+        if replay.is_none() {
+            solver.apply_events(&mut grid);
+        }
+
+        // Textures are reloaded when they change on disk, the grid is left alone
+        if texture_watcher.poll(&manifest) {
+            match TextureManifest::load(TEXTURE_FOLDER) {
+                Ok(reloaded) => manifest = reloaded,
+                Err(error) => println!("Keeping the old texture list. {}", error),
             }
-            if is_key_down(KeyCode::Minus) && zoom_x > 0.025 && zoom_y > 0.025 {
-                zoom_x *= 0.99;
-                zoom_y *= 0.99;
+            let (reloaded, texture_errors) = manifest.load_atlas().await;
+            atlas = reloaded;
+            texture_error = texture_errors.into_iter().next();
+            texture_renderer = TextureRenderer {atlas: atlas.clone()};
+            superposition_renderer = SuperpositionRenderer::new(atlas.clone());
+            autotile_renderer = AutotileRenderer::new(atlas.clone());
+            chunk_cache.clear();
+            // The manifest might list different files now
+            texture_watcher = TextureWatcher::new(&manifest);
+        }
+
+        // Tileset editor
+        if is_key_pressed(KeyCode::Tab) {
+            if editor.open {editor.open = false;} else {editor.show();}
+            browser.open = false;
+        }
+        if editor.open {
+            if editor.update(&atlas) {
+                // Renderers which scale by the number of options need to know the new tileset's
+                superposition_renderer = SuperpositionRenderer::new(atlas.clone());
+                heatmap = heatmap.map(|heatmap| HeatmapRenderer::new(heatmap.mode));
+                chunk_cache.clear();
+                heatmap_cache.clear();
+                solver.restart();
             }
-        }       
-        let effective_tilewidth = 32.0 * zoom_x;
-        let effective_tileheight = 32.0 * zoom_y;
+            next_frame().await;
+            continue;
+        }
+
+        // Adjacency browser
+        if is_key_pressed(KeyCode::J) {
+            browser.open = !browser.open;
+        }
+        if browser.open {
+            browser.update(&atlas);
+            next_frame().await;
+            continue;
+        }
+
+        // Panning and zooming only move the camera, the grid itself is left alone
+        camera.handle_input(&grid);
+        let effective_tilewidth = camera.tile_pixels();
+        let effective_tileheight = camera.tile_pixels();
 
         // Movement
+        if is_key_down(KeyCode::LeftShift) {
+            if is_key_pressed(KeyCode::Up) {solver.edit(|grid| grid.shift(0, 1));}
+            if is_key_pressed(KeyCode::Down) {solver.edit(|grid| grid.shift(0, -1));}
+            if is_key_pressed(KeyCode::Left) {solver.edit(|grid| grid.shift(1, 0));}
+            if is_key_pressed(KeyCode::Right) {solver.edit(|grid| grid.shift(-1, 0));}
+        }
         {
-            if is_key_pressed(KeyCode::Up) {grid.shift(0, 1);}
-            if is_key_pressed(KeyCode::Down) {grid.shift(0, -1);}
-            if is_key_pressed(KeyCode::Left) {grid.shift(1, 0);}
-            if is_key_pressed(KeyCode::Right) {grid.shift(-1, 0);}
-            if is_key_pressed(KeyCode::W) {grid.expand_to(grid.width, grid.height-1);}
-            if is_key_pressed(KeyCode::S) {grid.expand_to(grid.width, grid.height+1);}
-            if is_key_pressed(KeyCode::A) {grid.expand_to(grid.width-1, grid.height);}
-            if is_key_pressed(KeyCode::D) {grid.expand_to(grid.width+1, grid.height);}
+            if is_key_pressed(KeyCode::W) {solver.edit(|grid| grid.expand_to(grid.width, grid.height-1));}
+            if is_key_pressed(KeyCode::S) {solver.edit(|grid| grid.expand_to(grid.width, grid.height+1));}
+            if is_key_pressed(KeyCode::A) {solver.edit(|grid| grid.expand_to(grid.width-1, grid.height));}
+            if is_key_pressed(KeyCode::D) {solver.edit(|grid| grid.expand_to(grid.width+1, grid.height));}
         }
 
         // Mouse positioning
-        let mouse_x_pos = mouse_position().0;
-        let mouse_y_pos = mouse_position().1;
-        let mut mouse_x = ((mouse_x_pos - MARGIN_X) / effective_tilewidth) as i32;
-        let mut mouse_y = ((mouse_y_pos - MARGIN_Y) / effective_tileheight) as i32;
+        let (mut mouse_x, mut mouse_y) = camera.screen_to_tile(Vec2::from(mouse_position()));
         if mouse_y < 0 {mouse_y = 0;}
         if mouse_y >= grid.height {mouse_y = grid.height-1;}
         if mouse_x < 0 {mouse_x = 0;}
         if mouse_x >= grid.width {mouse_x = grid.width-1;}
 
+        // Reroll brush, E paints freehand and Q drags a rectangle. The region is rerolled when the mouse is released
+        let brushing = is_key_down(KeyCode::E) || is_key_down(KeyCode::Q);
+        if brushing && is_mouse_button_down(MouseButton::Left) {
+            if is_key_down(KeyCode::Q) {
+                let (anchor_x, anchor_y) = *brush_anchor.get_or_insert((mouse_x, mouse_y));
+                brush_cells.clear();
+                for i in min(anchor_x, mouse_x)..=max(anchor_x, mouse_x) {
+                    for j in min(anchor_y, mouse_y)..=max(anchor_y, mouse_y) {
+                        brush_cells.push((i, j));
+                    }
+                }
+            }
+            else if !brush_cells.contains(&(mouse_x, mouse_y)) {
+                brush_cells.push((mouse_x, mouse_y));
+            }
+        }
+        else if !brush_cells.is_empty() {
+            let region = std::mem::take(&mut brush_cells);
+            solver.edit(move |grid| grid.reroll_region(&region));
+            brush_anchor = None;
+        }
+
         // Mouse collapsing
-        if is_mouse_button_down(MouseButton::Left) {
-            grid.collapse_and_propegate(mouse_x, mouse_y);
+        if is_mouse_button_down(MouseButton::Left) && !brushing {
+            solver.edit(move |grid| grid.collapse_and_propegate(mouse_x, mouse_y));
         }
 
         // Forced collapsing
@@ -398,203 +878,152 @@ async fn main() {
                 if is_key_down(KeyCode::LeftShift) {num+=10;}
                 if is_key_down(KeyCode::LeftAlt) {num+=20;}
                 if is_key_down(KeyCode::LeftControl) {num+=40;}
-                let tile = grid.tilegrid[mouse_x as usize][mouse_y as usize].clone();
-                if num as usize <= tile.possible_tiles.len() {
-                    let tileopt = tile.possible_tiles[num as usize - 1];
-                    grid.tilegrid[mouse_x as usize][mouse_y as usize].possible_tiles = vec![tileopt];
-                    grid.restrict_and_propegate(vec![(mouse_x, mouse_y), (mouse_x, mouse_y-1), (mouse_x, mouse_y+1), (mouse_x-1, mouse_y), (mouse_x+1, mouse_y)])
-                }
+                solver.edit(move |grid| {
+                    if !grid.contains(mouse_x, mouse_y) {
+                        return Ok(());
+                    }
+                    let tile = grid.tilegrid[mouse_x as usize][mouse_y as usize].clone();
+                    if num as usize <= tile.possible_tiles.len() {
+                        let tileopt = tile.possible_tiles[num as usize - 1];
+                        grid.collapse_to(mouse_x, mouse_y, tileopt)?;
+                    }
+                    return Ok(());
+                });
             }
         }
         
         //Auto collapsing
-        if is_key_down(KeyCode::Space) {
-            for _ in 0..100 {// warp factor
-                let x;
-                let y;
-                let indices = grid.pick_index();
-                if indices.is_none() {
-                    continue;
-                }
-                (x, y) = indices.unwrap();
-                grid.collapse_and_propegate(x, y)
+        {
+            if is_key_pressed(KeyCode::Space) {
+                if solver.paused {solver.resume();} else {solver.pause();}
             }
+            if is_key_pressed(KeyCode::N) {solver.step();}
+            if is_key_pressed(KeyCode::RightBracket) {solver.set_rate(solver.rate.saturating_mul(2));}
+            if is_key_pressed(KeyCode::LeftBracket) {solver.set_rate(solver.rate / 2);}
+        }
+
+        // Parallel generation, done on the solver thread so the window keeps responding
+        if is_key_pressed(KeyCode::G) {
+            solver.edit(|grid| grid.generate_parallel(parallel::BLOCK_SIZE));
         }
 
         // Mouse Ungeneration
         if is_mouse_button_down(MouseButton::Right) {
-            grid.tilegrid[mouse_x as usize][mouse_y as usize] = UndecidedTile::new();
-            grid.unrestrict_grid();
-        }
-
-        // LOD/Zoom handling
-        let lod_x = 2 << ((zoom_x*32.0_f32).log2() as i32);
-        let lod_y = 2 << ((zoom_y*32.0_f32).log2() as i32);
-        let width_up = 2 << (grid.width.ilog2());
-        let height_up = 2 << (grid.height.ilog2());
-        let texture_width = min(width_up * lod_x, screen_width() as i32 * 2);
-        let texture_height = min(height_up * lod_y, screen_height() as i32 * 2);
-        if lod_x != grid.lod_x || lod_y != grid.lod_y || texture_width != grid.tilegrid_texture.texture.width() as i32 || texture_height != grid.tilegrid_texture.texture.height() as i32 {
-            let mut old_texture_width = grid.tilegrid_texture.texture.width();
-            let mut old_texture_height = grid.tilegrid_texture.texture.height();
-            let tilegrid_texture = render_target(texture_width as u32, texture_height as u32);
-            tilegrid_texture.texture.set_filter(FilterMode::Nearest);
-            set_camera(&Camera2D {
-                render_target: Some(tilegrid_texture.clone()),
-                .. Camera2D::from_display_rect(Rect::new(0.0, 0.0, tilegrid_texture.texture.width(), tilegrid_texture.texture.height()))
-            });
-            if lod_x > grid.lod_x || lod_y > grid.lod_y {
-                old_texture_height *= 2.0;
-                old_texture_width *= 2.0;
-            }
-            if lod_x < grid.lod_x || lod_y < grid.lod_y {
-                old_texture_height /= 2.0;
-                old_texture_width /= 2.0;
-            }
-            let screen_tiles_max = if screen_width()/lod_x as f32 > screen_height()/lod_y as f32 {screen_width()/lod_x as f32} else {screen_height()/lod_y as f32};
-            let (placeholder,  placeholder_lod) =
-            if screen_tiles_max >= 512.0/2.0 {
-                (placeholders[0].clone(), 2.0)
-            }
-            else if screen_tiles_max >= 512.0/4.0 {
-                (placeholders[1].clone(), 4.0)
-            }
-            else if screen_tiles_max >= 512.0/8.0 {
-                (placeholders[2].clone(), 8.0)
-            }
-            else if screen_tiles_max >= 512.0/16.0 {
-                (placeholders[3].clone(), 16.0)
-            }
-            else {
-                (placeholders[4].clone(), 32.0)
-            };
-            println!("width: {}, height: {}, width: {}, lod: {}", screen_width(), screen_height(), placeholder.texture.width(), placeholder_lod);
-            
-            let placeholder_draw_width = (placeholder.texture.width() / placeholder_lod) * lod_x as f32;
-            let placeholder_draw_height = (placeholder.texture.height() / placeholder_lod) * lod_y as f32;
-            println!("placeholder_draw_width: {}, placeholder_draw_height: {}", placeholder_draw_width, placeholder_draw_height);
-            draw_texture_ex(
-                &placeholder.texture, 
-                0.0, 
-                0.0, 
-                WHITE, 
-                DrawTextureParams {
-                    dest_size: Some(vec2(placeholder_draw_width, placeholder_draw_height)),
-                    flip_y: true,
-                    ..Default::default()
-                },
-            );
-            draw_texture_ex(
-                &grid.tilegrid_texture.texture,
-                0.0,
-                0.0,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(old_texture_width as f32, old_texture_height as f32)),
-                    flip_y: true,
-                    ..Default::default()
-                },
-            );
-            grid.tilegrid_texture = tilegrid_texture;
-            println!("lod_x: {}, lod_y: {}, texture_width: {}, texture_height: {}", lod_x, lod_y, texture_width, texture_height);
-            grid.lod_x = lod_x;
-            grid.lod_y = lod_y;
-        }
-
-        //Render to the placeholder textures for zooming
-        for (i, placeholder) in placeholders.iter().enumerate() {
-            let render_every = grid.width*grid.height/50 + 1;
-            set_camera(&Camera2D {
-                render_target: Some(placeholder.clone()),
-                .. Camera2D::from_display_rect(Rect::new(0.0, 0.0, placeholder.texture.width(), placeholder.texture.height()))
-            });
-            draw_tilegrid(&grid, &grid.texturemap, Vec2::new((2<<i) as f32, (2<<i) as f32), Vec2::new(placeholder.texture.width(), placeholder.texture.height()), framecount, render_every);
+            solver.edit(move |grid| grid.uncollapse(mouse_x, mouse_y));
         }
 
+        let renderer: &dyn Renderer = match rendermode {
+            Rendermode::Texture => &texture_renderer,
+            Rendermode::Superposition => &superposition_renderer,
+            Rendermode::Autotile => &autotile_renderer,
+            #[cfg(feature = "colored_wires")]
+            Rendermode::Wires => &WiresRenderer,
+            #[cfg(feature = "colored_wires")]
+            Rendermode::Triangles => &TrianglesRenderer,
+            _ => &DebugRenderer,
+        };
+
+        // Only tiles which have changed since the last frame are drawn again, everything else is already on the chunks
+        let dirty = grid.take_dirty();
+        chunk_cache.invalidate(&renderer.affected_tiles(&dirty));
+        heatmap_cache.invalidate(&dirty);
+
         //Main render
         {
-            set_camera(&Camera2D {
-                render_target: Some(grid.tilegrid_texture.clone()),
-                .. Camera2D::from_display_rect(Rect::new(0.0, 0.0, grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()))
-            });
-            // let render_every = (10000.0/((lod_x.ilog2()*lod_y.ilog2()) + 1) as f32) as i32 + 1; //Based on LOD
-            // let render_every = grid.width*grid.height/1000; //Based on fixed number of tiles per frame
-            let tiles_onscreen = (screen_width()/effective_tilewidth) as i32 * (screen_height()/effective_tileheight) as i32;
-            let render_every = (tiles_onscreen/100) as i32; //Based on number of tiles on screen
-            let render_every = max(5, render_every);
-            match grid.rendermode {
-                Rendermode::Texture => draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, render_every),
-                _ => debug_draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, render_every),
-            }
+            set_default_camera();
+            clear_background(BLACK);
+            chunk_cache.draw(&grid, renderer, &camera, WHITE);
         }
 
         //Rendermode switching
         {
             if is_key_pressed(KeyCode::I) {
-                grid.rendermode = Rendermode::Texture;
-                if grid.width*grid.height < 1000 {
-                    draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, 1)
-                }
+                rendermode = Rendermode::Texture;
+                chunk_cache.clear();
             }
             if is_key_pressed(KeyCode::O) {
-                grid.rendermode = Rendermode::Debug;
-                if grid.width*grid.height < 1000 {
-                    debug_draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, 1)
-                }
+                rendermode = Rendermode::Debug;
+                chunk_cache.clear();
+            }
+            if is_key_pressed(KeyCode::U) {
+                rendermode = Rendermode::Superposition;
+                chunk_cache.clear();
+            }
+            if is_key_pressed(KeyCode::L) {
+                rendermode = Rendermode::Autotile;
+                chunk_cache.clear();
+            }
+            #[cfg(feature = "colored_wires")]
+            if is_key_pressed(KeyCode::M) {
+                rendermode = Rendermode::Wires;
+                chunk_cache.clear();
+            }
+            #[cfg(feature = "colored_wires")]
+            if is_key_pressed(KeyCode::V) {
+                rendermode = Rendermode::Triangles;
+                chunk_cache.clear();
             }
         }
 
-        //Debug clear
+        //Debug clear, drops the rendered chunks so everything is drawn again
         if is_key_pressed(KeyCode::C) {
-            draw_rectangle(0.0, 0.0, 1000.0, 1000.0, GRAY)
+            chunk_cache.clear();
         }
 
-        //Grid
-        if is_key_pressed(KeyCode::P) {
-            grid.whitegrid = !grid.whitegrid;
-            if !grid.whitegrid { //redraw the grid without the white grid
-                match grid.rendermode {
-                    Rendermode::Texture => draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, 1),
-                    _ => debug_draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, 1),
-                }
+        //Heatmap, H cycles between options left, entropy, and off
+        if is_key_pressed(KeyCode::H) {
+            heatmap = match heatmap.map(|heatmap| heatmap.mode) {
+                None => Some(HeatmapRenderer::new(HeatmapMode::Options)),
+                Some(HeatmapMode::Options) => Some(HeatmapRenderer::new(HeatmapMode::Entropy)),
+                Some(HeatmapMode::Entropy) => None,
+            };
+            heatmap_cache.clear();
+            solver.watch_next_pick(heatmap.is_some());
+        }
+        if let Some(heatmap) = &heatmap {
+            heatmap_cache.draw(&grid, heatmap, &camera, Color::new(1.0, 1.0, 1.0, 0.6));
+            //Outline the tile the solver will collapse next
+            if let Some((i, j)) = solver.next_pick {
+                let corner = camera.world_to_screen(Vec2::new(i as f32, j as f32));
+                draw_rectangle_lines(corner.x, corner.y, effective_tilewidth, effective_tileheight, effective_tilewidth*0.15, MAGENTA);
             }
+            heatmap.draw_legend();
         }
-        if grid.whitegrid {
-            draw_whitegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), 0, 1);
+
+        //Grid, drawn over the chunks rather than onto them
+        if is_key_pressed(KeyCode::P) {
+            whitegrid = !whitegrid;
+        }
+        if whitegrid {
+            let tile_pixels = camera.tile_pixels();
+            // The screen, in pixels measured from the grid's top left corner
+            let screen_rect = Rect::new(camera.offset.x * tile_pixels, camera.offset.y * tile_pixels, screen_width(), screen_height());
+            set_camera(&Camera2D::from_display_rect(screen_rect));
+            WhitegridRenderer.draw_region(&grid, screen_rect, Vec2::splat(tile_pixels));
+            set_default_camera();
         }
 
         //Reset
         if is_key_pressed(KeyCode::R) {
-            let temp = grid.rendermode;
-            grid = TileGrid::new(grid.width, grid.height, texturemap.clone());
-            grid.rendermode = temp;
-            match grid.rendermode {
-                Rendermode::Texture => draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, 1),
-                _ => debug_draw_tilegrid(&grid, &grid.texturemap, Vec2::new(lod_x as f32, lod_y as f32), Vec2::new(grid.tilegrid_texture.texture.width(), grid.tilegrid_texture.texture.height()), framecount, 1),
-            }
+            solver.restart();
         }
 
-        //Render texture to screen
-        {
-            let width_a = 32.0*width_up as f32*zoom_x;
-            let height_a = 32.0*height_up as f32*zoom_y;
-            let width_b = (grid.tilegrid_texture.texture.width()/lod_x as f32)*32.0*zoom_x;
-            let height_b = (grid.tilegrid_texture.texture.height()/lod_y as f32)*32.0*zoom_y;
-            let width = if grid.tilegrid_texture.texture.width() == screen_width() * 2.0 {width_b} else {width_a};
-            let height = if grid.tilegrid_texture.texture.height() == screen_height() * 2.0 {height_b} else {height_a};
-            set_default_camera();
-            draw_texture_ex(
-                &grid.tilegrid_texture.texture,
-                MARGIN_X,
-                MARGIN_Y,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(width, height)),
-                    // dest_size: Some(vec2(screen_width(), screen_height())),
-                    flip_y: true,
-                    ..Default::default()
-                },
-            );
+        //Failures, the grid has already been put back how it was before whatever failed
+        if let Some(failure) = &solver.failure {
+            let options = if failure.can_backtrack {"R to restart, B to backtrack, Escape to carry on"} else {"R to restart, Escape to carry on"};
+            draw_error(&failure.error, options);
+            if failure.can_backtrack && is_key_pressed(KeyCode::B) {
+                solver.backtrack();
+            }
+            else if is_key_pressed(KeyCode::Escape) {
+                solver.failure = None;
+            }
+        }
+        else if let Some(error) = &texture_error {
+            draw_error(error, "Missing textures are drawn as magenta and black squares. Escape to carry on");
+            if is_key_pressed(KeyCode::Escape) {
+                texture_error = None;
+            }
         }
 
         // Mouse hovering
@@ -616,24 +1045,194 @@ async fn main() {
                 for (i, tile) in tiles.iter().enumerate() {
                     let x = (i as f32) * X_SPACING + L_PADDING;
                     let y = screen_height()-TILEHEIGHT-B_PADDING;
-                    draw_tile_opt( x, y, Vec2::new(TILEWIDTH, TILEHEIGHT), tile, &grid.texturemap);
+                    draw_tile_opt( x, y, Vec2::new(TILEWIDTH, TILEHEIGHT), tile, &atlas);
                     let text_x = if i+1 < 10 {(i as f32 + 0.65) * X_SPACING - TILEHEIGHT*0.15} else {(i as f32 + 0.65)* X_SPACING - TILEHEIGHT*0.35};
-                    draw_text(&format!("{}", i+1), text_x, screen_height()-TILEHEIGHT*1.4, TILEHEIGHT*0.7, WHITE);
+                    draw_text(format!("{}", i+1), text_x, screen_height()-TILEHEIGHT*1.4, TILEHEIGHT*0.7, WHITE);
                     if i%10 == 0 && i != 0 {
                         draw_line((i as f32 + 0.15) * X_SPACING, screen_height()-TILEHEIGHT*1.9, (i as f32 + 0.15) * X_SPACING, screen_height()-TILEHEIGHT*0.3, X_SPACING-TILEWIDTH, WHITE);
                     }
                 }
             }
+            //Outline the cells selected by the reroll brush
+            for (i, j) in brush_cells.iter() {
+                let corner = camera.world_to_screen(Vec2::new(*i as f32, *j as f32));
+                draw_rectangle_lines(corner.x, corner.y, effective_tilewidth, effective_tileheight, effective_tilewidth*0.1, YELLOW);
+            }
             //Draw an outline around the selected tile
-            draw_rectangle_lines(mouse_x as f32 * effective_tilewidth + MARGIN_X, mouse_y as f32 * effective_tileheight + MARGIN_Y, effective_tilewidth, effective_tileheight, effective_tilewidth*0.15, WHITE);
+            let corner = camera.world_to_screen(Vec2::new(mouse_x as f32, mouse_y as f32));
+            draw_rectangle_lines(corner.x, corner.y, effective_tilewidth, effective_tileheight, effective_tilewidth*0.15, WHITE);
+        }
+
+        //Socket inspector
+        if is_key_pressed(KeyCode::K) {
+            inspector = !inspector;
+        }
+        if inspector {
+            inspector::draw_inspector(&grid, &camera, mouse_x, mouse_y);
+        }
+
+        //Tracing, T starts and stops recording to trace.txt, Y starts and stops replaying it, and , . step through the replay
+        {
+            if is_key_pressed(KeyCode::T) {
+                recording = !recording;
+                if recording {solver.edit(|grid| {grid.start_trace(); return Ok(());});}
+                else {solver.edit(|grid| {grid.stop_trace(); return Ok(());});}
+            }
+            if is_key_pressed(KeyCode::Y) {
+                if let Some(finished) = replay.take() {
+                    finished.finish(&mut grid);
+                }
+                else {
+                    match Trace::load(TRACE_PATH) {
+                        Ok(trace) => match Replay::start(trace, &mut grid) {
+                            Ok(started) => replay = Some(started),
+                            Err(error) => println!("Couldn't replay {}: {}", TRACE_PATH, error),
+                        },
+                        Err(error) => println!("{}", error),
+                    }
+                }
+            }
+            if let Some(replay) = &mut replay {
+                let steps = if is_key_down(KeyCode::LeftShift) {100} else {1};
+                if is_key_pressed(KeyCode::Period) {
+                    for _ in 0..steps {
+                        if let Err(error) = replay.step_forward(&mut grid) {
+                            println!("Couldn't replay event {} of {}: {}", replay.position + 1, TRACE_PATH, error);
+                            break;
+                        }
+                    }
+                }
+                if is_key_pressed(KeyCode::Comma) {
+                    for _ in 0..steps {replay.step_back(&mut grid);}
+                }
+                let description = match replay.current() {
+                    Some(event) => event.to_line().chars().take(80).collect(),
+                    None => String::new(),
+                };
+                let text = format!("Replay {}/{}: {}", replay.position, replay.len(), description);
+                let width = measure_text(&text, None, 16, 1.0).width + 10.0;
+                draw_rectangle(screen_width()/2.0 - width/2.0, 0.0, width, 20.0, BLACK);
+                draw_text(&text, screen_width()/2.0 - width/2.0 + 5.0, 14.0, 16.0, WHITE);
+            }
+        }
+
+        //Solver status
+        {
+            let mut status = if solver.pending_edits > 0 {"Working...".to_string()}
+            else if solver.paused {"Paused".to_string()}
+            else {format!("{} tiles/s", solver.rate)};
+            if recording {status += " (rec)";}
+            draw_rectangle(screen_width()-140.0, 0.0, 140.0, 20.0, BLACK);
+            draw_text(&status, screen_width()-135.0, 14.0, 16.0, WHITE);
         }
 
         //FPS
         if is_key_down(KeyCode::F) {
             draw_rectangle(0.0, 0.0, 50.0, 20.0, BLACK);
-            draw_text(&format!("FPS: {}", get_fps()), 5.0, 10.0, 10.0, WHITE);
+            draw_text(format!("FPS: {}", get_fps()), 5.0, 10.0, 10.0, WHITE);
         }
-        framecount += 1;
         next_frame().await;
     }
 }
+
+// The tests use the castle's tiles, in a 3x1 grid so only the tiles either side of each other matter
+#[cfg(all(test, feature = "castle"))]
+mod tests {
+    use super::*;
+
+    fn tile (texture: &str) -> TileChoice {
+        return *tileset::current().tiles.iter().find(|tileopt| tileopt.texture == texture).unwrap();
+    }
+
+    fn textures (grid: &TileGrid, x: i32, y: i32) -> Vec<&'static str> {
+        return grid.tilegrid[x as usize][y as usize].possible_tiles.iter().map(|tileopt| tileopt.texture).collect();
+    }
+
+    #[test]
+    fn supported_options_only_fit_the_neighbours_that_arent_relaxed () {
+        let mut grid = TileGrid::new(3, 1).unwrap();
+        grid.collapse_to(0, 0, tile("Sky")).unwrap();
+        let supported: Vec<&str> = grid.supported_options(&tileset::current(), 1, 0, &HashSet::new()).iter().map(|tileopt| tileopt.texture).collect();
+        assert_eq!(supported, vec!["Wall L", "Wall TL", "Sky"]);
+        // Relaxing the left lets every tile back in, except the door's parts, which would hang off the top or bottom of the grid
+        let relaxed: Vec<&str> = grid.supported_options(&tileset::current(), 1, 0, &HashSet::from([(0, 0)])).iter().map(|tileopt| tileopt.texture).collect();
+        assert_eq!(relaxed, vec!["Wall", "Wall L", "Wall R", "Wall T", "Wall TL", "Wall TR", "Wall LC", "Wall RC", "Sky"]);
+    }
+
+    #[test]
+    fn uncollapsing_relaxes_the_tiles_that_depended_on_it () {
+        let mut grid = TileGrid::new(3, 1).unwrap();
+        grid.collapse_to(0, 0, tile("Sky")).unwrap();
+        grid.collapse_to(1, 0, tile("Sky")).unwrap();
+        assert_eq!(textures(&grid, 2, 0), vec!["Wall L", "Wall TL", "Sky"]);
+
+        grid.uncollapse(1, 0).unwrap();
+        // The decided tile on the left is left alone and still limits the middle, but nothing limits the right any more
+        assert_eq!(textures(&grid, 0, 0), vec!["Sky"]);
+        assert_eq!(textures(&grid, 1, 0), vec!["Wall L", "Wall TL", "Sky"]);
+        assert_eq!(textures(&grid, 2, 0), vec!["Wall", "Wall L", "Wall R", "Wall T", "Wall TL", "Wall TR", "Wall LC", "Wall RC", "Sky"]);
+    }
+
+    #[test]
+    fn uncollapsing_part_of_a_big_tile_clears_all_of_it () {
+        // The door's bottom is Ground, which only the bottom edge of the grid can be next to
+        let mut grid = TileGrid::new(4, 3).unwrap();
+        grid.collapse_to(1, 1, tile("Door 0,0")).unwrap();
+        assert_eq!([textures(&grid, 1, 1), textures(&grid, 2, 1), textures(&grid, 1, 2), textures(&grid, 2, 2)],
+            [vec!["Door 0,0"], vec!["Door 1,0"], vec!["Door 0,1"], vec!["Door 1,1"]]);
+
+        grid.uncollapse(2, 2).unwrap();
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            assert!(textures(&grid, x, y).len() > 1, "{},{} is still decided", x, y);
+        }
+        assert!(textures(&grid, 1, 1).contains(&"Door 0,0"));
+    }
+
+    #[test]
+    fn rerolling_a_region_only_changes_the_tiles_in_it () {
+        // Sky over the top of a wall, with a 2x2 region at the bottom that fits either four walls or the door
+        let mut grid = TileGrid::new(4, 4).unwrap();
+        for x in 0..4 {
+            for (y, texture) in ["Sky", "Wall T", "Wall", "Wall"].into_iter().enumerate() {
+                grid.collapse_to(x, y as i32, tile(texture)).unwrap();
+            }
+        }
+        let region = [(1, 2), (2, 2), (1, 3), (2, 3)];
+        let outside = |grid: &TileGrid| (0..4).flat_map(|x| (0..4).map(move |y| (x, y)))
+            .filter(|cell| !region.contains(cell)).map(|(x, y)| textures(grid, x, y)).collect::<Vec<_>>();
+        let before = outside(&grid);
+
+        let mut doors = 0;
+        for _ in 0..50 {
+            // Cells outside the grid and repeats are ignored
+            grid.reroll_region(&[(1, 2), (2, 2), (1, 3), (2, 3), (1, 2), (9, 9)]).unwrap();
+            assert_eq!(outside(&grid), before);
+            let rerolled: Vec<Vec<&str>> = region.iter().map(|&(x, y)| textures(&grid, x, y)).collect();
+            if rerolled == [vec!["Door 0,0"], vec!["Door 1,0"], vec!["Door 0,1"], vec!["Door 1,1"]] {
+                doors += 1;
+            }
+            else {
+                assert_eq!(rerolled, vec![vec!["Wall"]; 4]);
+            }
+        }
+        assert!(doors > 0 && doors < 50, "the door came up {} times out of 50", doors);
+    }
+
+    #[test]
+    fn windows_are_only_put_on_the_inside_of_walls () {
+        let mut grid = TileGrid::new(1, 3).unwrap();
+        grid.collapse_to(0, 0, tile("Sky")).unwrap();
+        grid.collapse_to(0, 1, tile("Wall T")).unwrap();
+        grid.collapse_to(0, 2, tile("Wall")).unwrap();
+        let decorations = TileGrid::layer_on(&grid, 0).unwrap();
+        assert_eq!(textures(&decorations, 0, 0), vec!["Nothing"]);
+        assert_eq!(textures(&decorations, 0, 1), vec!["Nothing"]);
+        assert_eq!(textures(&decorations, 0, 2), vec!["Nothing", "Window overlay"]);
+
+        // The decorations are free, so each is picked on its own and none of them can contradict
+        grid.generate_layers().unwrap();
+        assert!(!grid.layers_pending());
+        assert_eq!(textures(&grid.layers[0], 0, 0), vec!["Nothing"]);
+        assert_eq!(textures(&grid.layers[0], 0, 1), vec!["Nothing"]);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::*;
+use rayon::prelude::*;
+
+/// The width and height of the blocks the grid is split into when generating in parallel
+pub const BLOCK_SIZE: i32 = 16;
+/// How many more times a block that contradicts is generated again before the whole grid gives up
+pub const BLOCK_RETRIES: u32 = 5;
+
+/// A generated block as (bx, by, left, top, grid), where the grid is the block plus its border with its top left at (left, top)
+type Block = (i32, i32, i32, i32, TileGrid);
+
+/// Functions for generating the grid on multiple threads
+impl TileGrid {
+    /// Collapses the whole grid by splitting it into blocks and generating them on the rayon thread pool.
+    /// Blocks are done in diagonal waves from the top left, so no two blocks being generated at the same time share an edge,
+    /// and every block is generated against already decided blocks above and to the left of it, the same way a serial collapse grows.
+    /// Each block is generated on a copy of itself plus a one tile border, and once a wave is generated its blocks are written back
+    /// one at a time, reconciling the seams by propegating from the tiles bordering each block.
+    /// Blocks in the same wave meet at the corners of their borders, so one can leave a seam tile with nothing that fits the next.
+    /// That block is then generated again against what's already been written back, up to BLOCK_RETRIES times.
+    /// Each wave is written back atomically, so if one still contradicts the grid is left as the waves before it made it
+    pub fn generate_parallel (&mut self, block_size: i32) -> Result<(), WfcError> {
+        let blocks_x = (self.width + block_size - 1) / block_size;
+        let blocks_y = (self.height + block_size - 1) / block_size;
+        for wave in 0..blocks_x + blocks_y - 1 {
+            let mut blocks = Vec::<(i32, i32)>::new();
+            for bx in max(0, wave - blocks_y + 1)..min(blocks_x, wave + 1) {
+                blocks.push((bx, wave - bx));
+            }
+
+            let generated = blocks.par_iter()
+                .map(|&(bx, by)| self.generate_block(bx, by, block_size))
+                .collect::<Result<Vec<Block>, WfcError>>()?;
+
+            self.atomically(|grid| {
+                for block in generated {
+                    let (bx, by) = (block.0, block.1);
+                    let mut retries = 0;
+                    let mut result = grid.write_block(&block, block_size);
+                    while let Err(WfcError::Contradiction {..}) = result {
+                        if retries == BLOCK_RETRIES {
+                            break;
+                        }
+                        retries += 1;
+                        result = grid.generate_block(bx, by, block_size).and_then(|block| grid.write_block(&block, block_size));
+                    }
+                    result?;
+                }
+                return Ok(());
+            })?;
+        }
+        return Ok(());
+    }
+
+    /// Generates one block on a copy of it and its border. A block that contradicts is generated again with a new rng
+    /// up to BLOCK_RETRIES times, since most contradictions are just bad luck
+    fn generate_block (&self, bx: i32, by: i32, block_size: i32) -> Result<Block, WfcError> {
+        let left = max(0, bx * block_size - 1);
+        let top = max(0, by * block_size - 1);
+        let right = min(self.width, (bx + 1) * block_size + 1);
+        let bottom = min(self.height, (by + 1) * block_size + 1);
+        let mut block = self.sub_grid(left, top, right - left, bottom - top);
+
+        let mut region = Vec::<(i32, i32)>::new();
+        for i in bx * block_size..min(self.width, (bx + 1) * block_size) {
+            for j in by * block_size..min(self.height, (by + 1) * block_size) {
+                region.push((i - left, j - top));
+            }
+        }
+        // A failed collapse is rolled back, so the block only needs a new rng to try again
+        let mut retries = 0;
+        loop {
+            match block.collapse_region(&region) {
+                Ok(()) => return Ok((bx, by, left, top, block)),
+                Err(WfcError::Contradiction {..}) if retries < BLOCK_RETRIES => {
+                    retries += 1;
+                    block.rng = StdRng::from_entropy();
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Writes back only the inside of a block, the border was just a copy to generate against, then propegates from the tiles bordering it.
+    /// Leaves the grid as it was if the block doesn't fit what's around it
+    fn write_block (&mut self, block: &Block, block_size: i32) -> Result<(), WfcError> {
+        let (bx, by, left, top, block) = block;
+        let tileset = tileset::current();
+        return self.atomically(|grid| {
+            let mut seams = Vec::<(i32, i32)>::new();
+            for i in 0..block.width {
+                for j in 0..block.height {
+                    let (x, y) = (left + i, top + j);
+                    if x / block_size == *bx && y / block_size == *by {
+                        grid.set_tile(&tileset, x, y, block.tilegrid[i as usize][j as usize].clone());
+                    }
+                    else {
+                        seams.push((x, y));
+                    }
+                }
+            }
+            return grid.restrict_and_propegate(&tileset, seams);
+        }).map(|_| ());
+    }
+
+    /// Copies a rectangle of the grid into a new, smaller grid
+    fn sub_grid (&self, left: i32, top: i32, width: i32, height: i32) -> TileGrid {
+        let mut tilegrid = Vec::<Vec<UndecidedTile>>::new();
+        for i in left..left + width {
+            tilegrid.push(self.tilegrid[i as usize][top as usize..(top + height) as usize].to_vec());
+        }
+        return TileGrid {
+            tilegrid,
+            width,
+            height,
+            dirty: HashSet::new(),
+            rng: StdRng::from_entropy(),
+            trace: None,
+            changes: None,
+            variant_seed: self.variant_seed,
+            layers: Vec::new(),
+        };
+    }
+}
+
+// The test uses the castle's tiles, which rarely contradict, so a failure means the blocks weren't reconciled
+#[cfg(all(test, feature = "castle"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_grid_of_several_blocks_is_generated_completely () {
+        // 3x2 blocks, with the last column and row only partly filled
+        let mut grid = TileGrid::new(40, 20).unwrap();
+        grid.generate_parallel(16).unwrap();
+        assert!(grid.tilegrid.iter().flatten().all(|tile| tile.possible_tiles.len() == 1));
+    }
+}
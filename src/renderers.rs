@@ -3,12 +3,88 @@
 #![allow(unused_variables)]
 
 /// Draws rectangles for each connection, with tiny center triangles
-// pub mod wires; 
+#[cfg(feature = "colored_wires")]
+pub mod wires;
 /// Draws triangles for each connection
-// pub mod triangles;
+#[cfg(feature = "colored_wires")]
+pub mod triangles;
 /// Uses hashes to draw any possible tile with any connections in a (usually) different color
 pub mod debug_grid_draw;
 /// Draws a grid of white lines
 pub mod whitegrid; 
+/// Colours each tile by how undecided it is
+pub mod heatmap;
+/// Draws each tile's texture
+pub mod texture;
+/// Draws each tile as a blend of all its remaining options
+pub mod superposition;
+/// Packs tile textures into one texture
+pub mod atlas;
+/// Lists the textures in a tileset folder and watches them for changes
+pub mod manifest;
+/// Caches the rendered grid in chunks at a few zoom levels
+pub mod chunks;
+/// Draws many tiles from the atlas in one mesh
+pub mod batch;
+/// Draws a terrain grid with beach tiles picked from each tile's neighbours
+pub mod autotile;
 
-pub mod texture;
\ No newline at end of file
+use crate::*;
+
+/// A way of drawing the tilegrid. Positions are in pixels on whatever is being drawn to, with the grid's top left corner at 0, 0
+pub trait Renderer {
+    /// Draws the tile at grid index (i, j) with its top left corner at (x, y)
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2);
+
+    /// Draws every tile that intersects view_rect, without looking at any of the tiles outside it
+    fn draw_region (&self, grid: &TileGrid, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        for i in left..right {
+            for j in top..bottom {
+                self.draw_tile(grid, i, j, i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size);
+            }
+        }
+    }
+
+    /// Draws only the given tiles, skipping any which don't intersect view_rect
+    fn draw_tiles (&self, grid: &TileGrid, tiles: &HashSet<(i32, i32)>, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        for &(i, j) in tiles.iter() {
+            if i < left || i >= right || j < top || j >= bottom {
+                continue;
+            }
+            self.draw_tile(grid, i, j, i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size);
+        }
+    }
+
+    /// The tiles that have to be drawn again when the given tiles change, which is only those tiles
+    /// unless the renderer draws a tile differently depending on its neighbours
+    fn affected_tiles (&self, tiles: &HashSet<(i32, i32)>) -> HashSet<(i32, i32)> {
+        return tiles.clone();
+    }
+}
+
+/// The option as it's drawn at grid index (i, j), with its texture swapped for the variant picked for that position
+pub fn with_variant (tileset: &Tileset, grid: &TileGrid, i: i32, j: i32, tileopt: &TileChoice) -> TileChoice {
+    return TileChoice {texture: tileset.variant(tileopt.texture, grid.variant_seed, i, j), ..*tileopt};
+}
+
+/// Where a texture is in the atlas. A part of a big tile is its share of the big tile's image
+pub fn source_rect (tileset: &Tileset, atlas: &atlas::Atlas, texture: &'static str) -> Rect {
+    return match tileset.part(texture) {
+        Some(part) => {
+            let big = &tileset.big_tiles[part.big];
+            big.part_rect(atlas.rect(tileset.preview(big.texture)), part)
+        }
+        None => atlas.rect(texture),
+    };
+}
+
+/// Returns the range of tile indices which intersect view_rect as (left, top, right, bottom), with right and bottom exclusive
+pub fn visible_tiles (grid: &TileGrid, view_rect: Rect, tile_size: Vec2) -> (i32, i32, i32, i32) {
+    let left = max(0, (view_rect.x / tile_size.x).floor() as i32);
+    let top = max(0, (view_rect.y / tile_size.y).floor() as i32);
+    let right = min(grid.width, (view_rect.right() / tile_size.x).ceil() as i32);
+    let bottom = min(grid.height, (view_rect.bottom() / tile_size.y).ceil() as i32);
+    return (left, top, right, bottom);
+}
@@ -0,0 +1,134 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// The widest the atlas can get before images start a new row
+const ATLAS_WIDTH: u32 = 1024;
+
+/// Every tile image packed into one texture, so tiles can all be drawn from the same texture.
+/// Each image has its edge pixels repeated around it, so sampling just outside a tile never picks up its neighbour
+#[derive(Clone)]
+pub struct Atlas {
+    pub texture: Texture2D,
+    /// Where each image is on the atlas, in pixels
+    rects: HashMap<&'static str, Rect>,
+    missing: Rect,
+}
+
+impl Atlas {
+    /// Packs the images into rows, keeping them in the order given
+    pub fn new (images: &[(&'static str, Image)], missing_image: &Image) -> Self {
+        // The missing texture goes last, after every keyed image
+        let all: Vec<&Image> = images.iter().map(|(_, image)| image).chain([missing_image]).collect();
+        let mut placements = Vec::new();
+        let mut x = 0;
+        let mut y = 0;
+        let mut row_height = 0;
+        let mut atlas_width = 0;
+        for image in all.iter() {
+            let (width, height) = (image.width() as u32 + 2, image.height() as u32 + 2);
+            if x + width > ATLAS_WIDTH && x != 0 {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            placements.push((x + 1, y + 1));
+            x += width;
+            row_height = max(row_height, height);
+            atlas_width = max(atlas_width, x);
+        }
+        let atlas_height = y + row_height;
+
+        let mut atlas_image = Image::gen_image_color(atlas_width as u16, atlas_height as u16, BLANK);
+        let mut rects = HashMap::new();
+        let mut missing = Rect::default();
+        for (i, image) in all.iter().enumerate() {
+            let (left, top) = placements[i];
+            let (width, height) = (image.width() as i32, image.height() as i32);
+            // Copies the image along with a one pixel border of its own edge pixels
+            for dx in -1..=width {
+                for dy in -1..=height {
+                    let source_x = dx.clamp(0, width - 1) as u32;
+                    let source_y = dy.clamp(0, height - 1) as u32;
+                    atlas_image.set_pixel((left as i32 + dx) as u32, (top as i32 + dy) as u32, image.get_pixel(source_x, source_y));
+                }
+            }
+            let rect = Rect::new(left as f32, top as f32, width as f32, height as f32);
+            match images.get(i) {
+                Some((key, _)) => {rects.insert(*key, rect);}
+                None => missing = rect,
+            }
+        }
+
+        let texture = Texture2D::from_image(&atlas_image);
+        texture.set_filter(FilterMode::Nearest);
+        return Self {texture, rects, missing};
+    }
+
+    /// Every key with an image, in alphabetical order
+    pub fn keys (&self) -> Vec<&'static str> {
+        let mut keys: Vec<&'static str> = self.rects.keys().copied().collect();
+        keys.sort();
+        return keys;
+    }
+
+    /// The part of the atlas holding the image for key, or the missing texture if there isn't one
+    pub fn rect (&self, key: &str) -> Rect {
+        return *self.rects.get(key).unwrap_or(&self.missing);
+    }
+}
+
+/// A magenta and black checkerboard, for when missing.png can't be loaded either
+pub fn fallback_missing_image () -> Image {
+    let mut image = Image::gen_image_color(16, 16, BLACK);
+    for x in 0..16 {
+        for y in 0..16 {
+            if (x / 8 + y / 8) % 2 == 0 {
+                image.set_pixel(x, y, MAGENTA);
+            }
+        }
+    }
+    return image;
+}
+
+/// Loads missing.png, falling back to a generated checkerboard
+pub async fn load_missing_image () -> Result<Image, (Image, WfcError)> {
+    return load_image("missing.png").await
+        .map_err(|_| (fallback_missing_image(), WfcError::MissingTexture {path: "missing.png".to_string()}));
+}
+
+/// Loads images from the given paths and packs them into an atlas, using the associated keys to look them up.
+/// Images that can't be loaded are drawn with missing.png, and are returned as errors alongside the atlas
+/// Usage:
+/// ```no_run
+/// let (atlas, errors) = load_atlas_paths(&["assets/wall.png","assets/floor.png"], &["wall","floor"]).await;
+/// draw_texture_ex(&atlas.texture, 0.0, 0.0, WHITE, DrawTextureParams {source: Some(atlas.rect("wall")), ..Default::default()});
+/// ```
+pub async fn load_atlas_paths (paths: &[&str], keys: &[&'static str]) -> (Atlas, Vec<WfcError>) {
+
+    let mut errors = Vec::new();
+    let missing_image = match load_missing_image().await {
+        Ok(image) => image,
+        Err((image, error)) => {
+            errors.push(error);
+            image
+        }
+    };
+    let mut images = Vec::new();
+
+    for (i, key) in keys.iter().enumerate() {
+        if let Some(path) = paths.get(i) {
+            if let Ok(image) = load_image(path).await {
+                images.push((*key, image));
+            }
+            else {
+                println!("Failed to load texture from path: {}", path);
+                errors.push(WfcError::MissingTexture {path: path.to_string()});
+            }
+        }
+        else {
+            println!("Not enough paths provided to load_atlas_paths");
+            errors.push(WfcError::MissingTexture {path: format!("(no path for {})", key)});
+        }
+    }
+    return (Atlas::new(&images, &missing_image), errors);
+}
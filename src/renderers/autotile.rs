@@ -0,0 +1,208 @@
+use crate::*;
+use crate::renderers::{Renderer, visible_tiles, with_variant};
+use crate::renderers::atlas::Atlas;
+use crate::renderers::batch::SpriteBatch;
+
+/// The kinds of ground the terrain wavefunction decides between. A tile's terrain is read from its texture's name,
+/// so the renderer works with any wavefunction whose tiles are named after them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Terrain {
+    Land,
+    Sand,
+    Water,
+}
+
+impl Terrain {
+    pub fn from_texture (texture: &str) -> Option<Self> {
+        return match texture {
+            "land" => Some(Terrain::Land),
+            "sand" => Some(Terrain::Sand),
+            "water" => Some(Terrain::Water),
+            _ => None,
+        };
+    }
+}
+
+/// The textures for sand next to water, with the corners they're dry in. Corners are bits clockwise from the top left,
+/// so 1 is the top left and 3 is the whole top edge. Every other corner pattern is one of these turned
+const COAST_TEXTURES: [(&str, usize); 3] = [
+    ("beach_water_corner", 0b0001),
+    ("beach", 0b0011),
+    ("beach_land_corner", 0b0111),
+];
+
+/// A texture and how many times it's turned 90 degrees clockwise
+type Piece = (&'static str, usize);
+
+/// Draws a terrain grid as coastlines. Land and water are drawn as they are, and each sand tile picks a beach texture
+/// from which of its corners touch water (Wang 2-corner tiles), so the coast follows the shape of the terrain
+/// without the wavefunction needing a tile for every piece of it.
+/// Undecided tiles are left empty, and decided tiles that aren't terrain are drawn with their own texture
+pub struct AutotileRenderer {
+    pub atlas: Atlas,
+    /// The piece to draw sand with, indexed by its dry corners
+    coast: [Piece; 16],
+}
+
+/// Turns a set of corners 90 degrees clockwise
+fn turn_corners (corners: usize) -> usize {
+    return ((corners << 1) | (corners >> 3)) & 0b1111;
+}
+
+/// The piece to draw sand with for each set of dry corners
+fn coast_pieces () -> [Piece; 16] {
+    let mut coast = [("sand", 0); 16];
+    for (texture, corners) in COAST_TEXTURES {
+        let mut turned = corners;
+        for turns in 0..4 {
+            coast[turned] = (texture, turns);
+            turned = turn_corners(turned);
+        }
+    }
+    // There's no texture for water on two opposite corners, so it's drawn as if only one of them was water
+    coast[0b0101] = coast[0b0111];
+    coast[0b1010] = coast[0b1110];
+    // Sand with water all round is a sandbar, and sand with no water next to it is plain sand
+    coast[0b0000] = ("sand", 0);
+    coast[0b1111] = ("sand", 0);
+    return coast;
+}
+
+impl AutotileRenderer {
+    pub fn new (atlas: Atlas) -> Self {
+        return Self {atlas, coast: coast_pieces()};
+    }
+
+    /// The terrain of a tile, if it's decided and is one
+    fn terrain (grid: &TileGrid, i: i32, j: i32) -> Option<Terrain> {
+        if i < 0 || j < 0 || i >= grid.width || j >= grid.height {
+            return None;
+        }
+        let tile = &grid.tilegrid[i as usize][j as usize];
+        if tile.possible_tiles.len() != 1 {
+            return None;
+        }
+        return Terrain::from_texture(tile.possible_tiles[0].texture);
+    }
+
+    /// The corners of a tile that don't touch water, as bits clockwise from the top left.
+    /// A corner touches water if any of the three other tiles that share it are water
+    fn dry_corners (grid: &TileGrid, i: i32, j: i32) -> usize {
+        let mut dry = 0;
+        for (bit, (dx, dy)) in [(-1, -1), (1, -1), (1, 1), (-1, 1)].into_iter().enumerate() {
+            let wet = [(dx, 0), (0, dy), (dx, dy)].iter().any(|&(ox, oy)| Self::terrain(grid, i + ox, j + oy) == Some(Terrain::Water));
+            if !wet {
+                dry |= 1 << bit;
+            }
+        }
+        return dry;
+    }
+
+    /// The autotiling pass for one tile, which is the tile as it should be drawn, or None if there's nothing to draw yet
+    pub fn autotile (&self, tileset: &Tileset, grid: &TileGrid, i: i32, j: i32) -> Option<TileChoice> {
+        let tile = &grid.tilegrid[i as usize][j as usize];
+        if tile.possible_tiles.len() != 1 {
+            return None;
+        }
+        let tileopt = &tile.possible_tiles[0];
+        let (texture, turns) = match Terrain::from_texture(tileopt.texture) {
+            None => return Some(with_variant(tileset, grid, i, j, tileopt)),
+            Some(Terrain::Sand) => self.coast[Self::dry_corners(grid, i, j)],
+            Some(_) => (tileopt.texture, 0),
+        };
+        // Textures are flipped before they're turned, so turning twice is the same as flipping both ways
+        let (flipx, flipy, rot90) = (turns >= 2, turns >= 2, turns % 2 == 1);
+        return Some(TileChoice {texture, flipx, flipy, rot90, ..*tileopt});
+    }
+}
+
+impl Renderer for AutotileRenderer {
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2) {
+        if let Some(tileopt) = self.autotile(&tileset::current(), grid, i, j) {
+            draw_tile_opt(x, y, tile_size, &tileopt, &self.atlas);
+        }
+    }
+
+    fn draw_region (&self, grid: &TileGrid, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        let tileset = tileset::current();
+        let mut batch = SpriteBatch::new(&self.atlas);
+        for i in left..right {
+            for j in top..bottom {
+                if let Some(tileopt) = self.autotile(&tileset, grid, i, j) {
+                    batch.push(i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size, &tileopt);
+                }
+            }
+        }
+    }
+
+    /// A tile's look depends on the tiles around it, so the neighbours of every changed tile are drawn again too
+    fn affected_tiles (&self, tiles: &HashSet<(i32, i32)>) -> HashSet<(i32, i32)> {
+        let mut affected = HashSet::new();
+        for &(i, j) in tiles.iter() {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    affected.insert((i + dx, j + dy));
+                }
+            }
+        }
+        return affected;
+    }
+
+    fn draw_tiles (&self, grid: &TileGrid, tiles: &HashSet<(i32, i32)>, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        let tileset = tileset::current();
+        let mut batch = SpriteBatch::new(&self.atlas);
+        for &(i, j) in tiles.iter() {
+            if i < left || i >= right || j < top || j >= bottom {
+                continue;
+            }
+            let (x, y) = (i as f32 * tile_size.x, j as f32 * tile_size.y);
+            match self.autotile(&tileset, grid, i, j) {
+                Some(tileopt) => batch.push(x, y, tile_size, &tileopt),
+                // Clear whatever was drawn there before, since an empty tile doesn't draw over it
+                None => draw_rectangle(x, y, tile_size.x, tile_size.y, BLACK),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turning_corners_moves_each_one_clockwise () {
+        assert_eq!(turn_corners(0b0001), 0b0010);
+        assert_eq!(turn_corners(0b0011), 0b0110);
+        assert_eq!(turn_corners(0b1000), 0b0001);
+        assert_eq!(turn_corners(0b1001), 0b0011);
+        for corners in 0..16 {
+            let turned = (0..4).fold(corners, |turned, _| turn_corners(turned));
+            assert_eq!(turned, corners);
+        }
+    }
+
+    #[test]
+    fn every_set_of_dry_corners_has_a_piece () {
+        let expected: [Piece; 16] = [
+            ("sand", 0),                   // 0000, water all round
+            ("beach_water_corner", 0),     // 0001
+            ("beach_water_corner", 1),     // 0010
+            ("beach", 0),                  // 0011
+            ("beach_water_corner", 2),     // 0100
+            ("beach_land_corner", 0),      // 0101, drawn as 0111
+            ("beach", 1),                  // 0110
+            ("beach_land_corner", 0),      // 0111
+            ("beach_water_corner", 3),     // 1000
+            ("beach", 3),                  // 1001
+            ("beach_land_corner", 1),      // 1010, drawn as 1110
+            ("beach_land_corner", 3),      // 1011
+            ("beach", 2),                  // 1100
+            ("beach_land_corner", 2),      // 1101
+            ("beach_land_corner", 1),      // 1110
+            ("sand", 0),                   // 1111, no water
+        ];
+        assert_eq!(coast_pieces(), expected);
+    }
+}
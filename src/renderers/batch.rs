@@ -0,0 +1,91 @@
+use crate::*;
+use crate::renderers::atlas::Atlas;
+use crate::renderers::source_rect;
+use crate::big_tiles::BigTile;
+use std::sync::Arc;
+use macroquad::models::Vertex;
+
+/// The most tiles drawn in one mesh. Each tile is a quad of 4 vertices, and mesh indices are u16
+pub const MAX_QUADS: usize = 16000;
+
+/// Raises macroquad's draw call size so a full batch fits in one draw call, it's too small for that by default
+pub fn set_drawcall_capacity () {
+    gl_set_drawcall_buffer_capacity(MAX_QUADS * 4 + 1, MAX_QUADS * 6 + 1);
+}
+
+/// Collects tiles into meshes drawn from the atlas, so a whole region of tiles takes a handful of draw calls
+pub struct SpriteBatch<'a> {
+    atlas: &'a Atlas,
+    /// The tileset when the batch was made, for finding where parts of big tiles are in the atlas
+    tileset: Arc<Tileset>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+}
+
+impl<'a> SpriteBatch<'a> {
+    pub fn new (atlas: &'a Atlas) -> Self {
+        return Self {
+            atlas,
+            tileset: tileset::current(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+    }
+
+    /// Adds a tile at the given XY co-ords and size, reoriented the same way draw_tile_opt does it
+    pub fn push (&mut self, x: f32, y: f32, tile_size: Vec2, tileopt: &TileChoice) {
+        self.push_tinted(x, y, tile_size, tileopt, WHITE);
+    }
+
+    /// Adds a tile multiplied by color, which can be used to draw it partly transparent
+    pub fn push_tinted (&mut self, x: f32, y: f32, tile_size: Vec2, tileopt: &TileChoice, color: Color) {
+        let source = source_rect(&self.tileset, self.atlas, tileopt.texture);
+        self.push_quad(x, y, tile_size, source, (tileopt.flipx, tileopt.flipy, tileopt.rot90), color);
+    }
+
+    /// Adds the whole image of a big tile, with its top left corner at the given XY co-ords and size being the size of every cell it covers
+    pub fn push_big (&mut self, x: f32, y: f32, size: Vec2, big: &BigTile) {
+        let source = self.atlas.rect(self.tileset.preview(big.texture));
+        self.push_quad(x, y, size, source, (false, false, false), WHITE);
+    }
+
+    /// Adds a quad showing source from the atlas, reoriented by (flipx, flipy, rot90) like a tile
+    fn push_quad (&mut self, x: f32, y: f32, tile_size: Vec2, source: Rect, (flipx, flipy, rot90): (bool, bool, bool), color: Color) {
+        if self.vertices.len() >= MAX_QUADS * 4 {
+            self.flush();
+        }
+        let atlas_size = self.atlas.texture.size();
+        let first = self.vertices.len() as u16;
+        // Corners go clockwise from the top left, as (0 or 1, 0 or 1) across the tile
+        for (dx, dy) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            // The texture is flipped then rotated 90 degrees clockwise, so undo that to find which part of the texture lands here
+            let (mut u, mut v) = if rot90 {(dy, 1.0 - dx)} else {(dx, dy)};
+            if flipx {u = 1.0 - u;}
+            if flipy {v = 1.0 - v;}
+            self.vertices.push(Vertex::new(
+                x + dx * tile_size.x, y + dy * tile_size.y, 0.0,
+                (source.x + u * source.w) / atlas_size.x, (source.y + v * source.h) / atlas_size.y,
+                color,
+            ));
+        }
+        self.indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    /// Draws every tile pushed since the last flush
+    pub fn flush (&mut self) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        draw_mesh(&Mesh {
+            vertices: std::mem::take(&mut self.vertices),
+            indices: std::mem::take(&mut self.indices),
+            texture: Some(self.atlas.texture.clone()),
+        });
+    }
+}
+
+impl Drop for SpriteBatch<'_> {
+    fn drop (&mut self) {
+        self.flush();
+    }
+}
@@ -0,0 +1,186 @@
+use crate::*;
+use crate::renderers::Renderer;
+use crate::camera::Camera;
+use std::collections::HashMap;
+
+/// The number of tiles along each side of a chunk
+pub const CHUNK_TILES: i32 = 32;
+/// The number of pixels each tile takes up on a chunk at each zoom level. Chunks are drawn at the smallest level
+/// that is at least as big as the tiles on screen, so they are only ever shrunk by up to half when drawn
+pub const LEVELS: [i32; 4] = [2, 4, 8, 16];
+/// The most pixels the cached chunks can take up, chunks that weren't drawn this frame are dropped past this
+const MAX_CACHED_PIXELS: usize = 1 << 24;
+/// The most tiles drawn onto new chunks each frame, so zooming out over a big grid doesn't freeze the window.
+/// Chunks that miss out are stood in for by the same chunk at another level, if there is one
+const MAX_TILES_PER_FRAME: usize = 1 << 16;
+
+/// A part of the grid rendered onto its own texture at one zoom level
+struct Chunk {
+    target: RenderTarget,
+    /// Tiles which have changed since the chunk was drawn
+    pending: HashSet<(i32, i32)>,
+    /// Whether the whole chunk needs to be drawn again
+    stale: bool,
+    /// The frame the chunk was last shown on
+    last_used: u64,
+}
+
+/// Keeps the grid rendered in chunks at a few zoom levels, so each frame only the chunks on screen are drawn,
+/// and only the tiles that changed are drawn onto them again.
+/// Memory use depends on how much fits on screen, not on the size of the grid
+pub struct ChunkCache {
+    /// Chunks indexed by (chunk x, chunk y, level)
+    chunks: HashMap<(i32, i32, i32), Chunk>,
+    frame: u64,
+    /// The size of the grid the chunks were drawn from, chunks are dropped when it changes since they'd hold tiles that are gone
+    grid_size: (i32, i32),
+}
+
+impl ChunkCache {
+    pub fn new () -> Self {
+        return Self {
+            chunks: HashMap::new(),
+            frame: 0,
+            grid_size: (0, 0),
+        };
+    }
+
+    /// Drops every chunk, so everything is drawn again from scratch
+    pub fn clear (&mut self) {
+        self.chunks.clear();
+    }
+
+    /// Marks tiles as needing to be drawn again on every chunk they're on
+    pub fn invalidate (&mut self, tiles: &HashSet<(i32, i32)>) {
+        if tiles.is_empty() || self.chunks.is_empty() {
+            return;
+        }
+        for &(x, y) in tiles.iter() {
+            for level in LEVELS {
+                if let Some(chunk) = self.chunks.get_mut(&(x.div_euclid(CHUNK_TILES), y.div_euclid(CHUNK_TILES), level)) {
+                    if chunk.stale {
+                        continue;
+                    }
+                    chunk.pending.insert((x, y));
+                    // Past this point it's cheaper to draw the whole chunk
+                    if chunk.pending.len() as i32 > CHUNK_TILES * CHUNK_TILES / 4 {
+                        chunk.pending.clear();
+                        chunk.stale = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws the part of the grid the camera can see to the screen multiplied by color, bringing the chunks on screen up to date first
+    pub fn draw (&mut self, grid: &TileGrid, renderer: &dyn Renderer, camera: &Camera, color: Color) {
+        self.frame += 1;
+        if self.grid_size != (grid.width, grid.height) {
+            self.clear();
+            self.grid_size = (grid.width, grid.height);
+        }
+        let level = level_for(camera.tile_pixels());
+        let view = camera.visible_rect();
+        let left = max(0, view.x.div_euclid(CHUNK_TILES as f32) as i32);
+        let top = max(0, view.y.div_euclid(CHUNK_TILES as f32) as i32);
+        let right = min((grid.width + CHUNK_TILES - 1) / CHUNK_TILES, (view.right() / CHUNK_TILES as f32).ceil() as i32);
+        let bottom = min((grid.height + CHUNK_TILES - 1) / CHUNK_TILES, (view.bottom() / CHUNK_TILES as f32).ceil() as i32);
+
+        // Bring the visible chunks up to date, picking which chunk each one will be shown with
+        let mut tile_budget = MAX_TILES_PER_FRAME;
+        let mut shown = Vec::new();
+        for cx in left..right {
+            for cy in top..bottom {
+                let key = (cx, cy, level);
+                if !self.chunks.contains_key(&key) && tile_budget > 0 {
+                    let target = render_target((CHUNK_TILES * level) as u32, (CHUNK_TILES * level) as u32);
+                    target.texture.set_filter(FilterMode::Nearest);
+                    self.chunks.insert(key, Chunk {target, pending: HashSet::new(), stale: true, last_used: 0});
+                }
+                let key = if self.chunks.contains_key(&key) {key}
+                else if let Some(fallback) = LEVELS.iter().map(|&other| (cx, cy, other)).find(|other| self.chunks.contains_key(other)) {fallback}
+                else {continue};
+
+                let chunk = self.chunks.get_mut(&key).unwrap();
+                chunk.last_used = self.frame;
+                if chunk.stale && tile_budget > 0 {
+                    render_chunk(grid, renderer, key, chunk);
+                    tile_budget = tile_budget.saturating_sub((CHUNK_TILES * CHUNK_TILES) as usize);
+                }
+                else if !chunk.pending.is_empty() {
+                    render_chunk(grid, renderer, key, chunk);
+                }
+                shown.push(key);
+            }
+        }
+
+        set_default_camera();
+        for key in shown.iter() {
+            let (cx, cy, _) = *key;
+            let corner = camera.world_to_screen(Vec2::new((cx * CHUNK_TILES) as f32, (cy * CHUNK_TILES) as f32));
+            draw_texture_ex(
+                &self.chunks[key].target.texture,
+                corner.x,
+                corner.y,
+                color,
+                DrawTextureParams {
+                    dest_size: Some(Vec2::splat(CHUNK_TILES as f32 * camera.tile_pixels())),
+                    flip_y: true,
+                    ..Default::default()
+                },
+            );
+        }
+
+        self.evict();
+    }
+
+    /// Drops the least recently shown chunks until the cache fits in MAX_CACHED_PIXELS, never dropping chunks on screen
+    fn evict (&mut self) {
+        let mut pixels: usize = self.chunks.keys().map(|&(_, _, level)| chunk_pixels(level)).sum();
+        if pixels <= MAX_CACHED_PIXELS {
+            return;
+        }
+        let mut unused: Vec<((i32, i32, i32), u64)> = self.chunks.iter()
+            .filter(|(_, chunk)| chunk.last_used != self.frame)
+            .map(|(key, chunk)| (*key, chunk.last_used))
+            .collect();
+        unused.sort_by_key(|(_, last_used)| *last_used);
+        for (key, _) in unused {
+            if pixels <= MAX_CACHED_PIXELS {
+                break;
+            }
+            self.chunks.remove(&key);
+            pixels -= chunk_pixels(key.2);
+        }
+    }
+}
+
+/// The level chunks should be drawn at for tiles this many pixels wide on screen
+fn level_for (tile_pixels: f32) -> i32 {
+    return *LEVELS.iter().find(|&&level| level as f32 >= tile_pixels).unwrap_or(&LEVELS[LEVELS.len() - 1]);
+}
+
+fn chunk_pixels (level: i32) -> usize {
+    return ((CHUNK_TILES * level) * (CHUNK_TILES * level)) as usize;
+}
+
+/// Draws a chunk's stale or pending tiles onto its texture
+fn render_chunk (grid: &TileGrid, renderer: &dyn Renderer, key: (i32, i32, i32), chunk: &mut Chunk) {
+    let (cx, cy, level) = key;
+    let tile_size = Vec2::splat(level as f32);
+    let rect = Rect::new((cx * CHUNK_TILES * level) as f32, (cy * CHUNK_TILES * level) as f32, (CHUNK_TILES * level) as f32, (CHUNK_TILES * level) as f32);
+    set_camera(&Camera2D {
+        render_target: Some(chunk.target.clone()),
+        .. Camera2D::from_display_rect(rect)
+    });
+    if chunk.stale {
+        // Anything past the edge of the grid stays black
+        clear_background(BLACK);
+        renderer.draw_region(grid, rect, tile_size);
+    }
+    else {
+        renderer.draw_tiles(grid, &chunk.pending, rect, tile_size);
+    }
+    chunk.pending.clear();
+    chunk.stale = false;
+}
@@ -1,39 +1,33 @@
 use crate::*;
+use crate::renderers::Renderer;
 
-const MARGIN1A: f32 = 0.25; ///Distance from the edge of the tile to the edge of the tile identifier square
-const MARGIN1B: f32 = 1.0-(2.0*MARGIN1A); ///Size of the tile identifier square
-const MARGIN2A: f32 = 0.00; ///Distance from the edge of the tile to the edge of the connection triangles
-const MARGIN2B: f32 = 1.0-MARGIN2A; ///Opposite corner of the triangle
+///Distance from the edge of the tile to the edge of the tile identifier square
+const MARGIN1A: f32 = 0.25;
+///Size of the tile identifier square
+const MARGIN1B: f32 = 1.0-(2.0*MARGIN1A);
+///Distance from the edge of the tile to the edge of the connection triangles
+const MARGIN2A: f32 = 0.00;
+///Opposite corner of the triangle
+const MARGIN2B: f32 = 1.0-MARGIN2A;
 
 use std::{collections::hash_map::DefaultHasher, hash::{Hash, Hasher}};
 
-pub fn draw_tilegrid (grid: &TileGrid, textures: &HashMap<&str, Texture2D>, tile_size: Vec2, texture_limits: Vec2, offset: i32, render_every: i32) {
-    for i in 0..grid.width {
-        for j in 0..grid.height {
-            if (i*101 + j*5)%render_every != offset%render_every {
-                continue;
-            }
-            let tx = (i as f32) * tile_size.x;
-            let ty = (j as f32) * tile_size.y;
-            // don't render tiles that are offscreen
-            if tx < -tile_size.x || ty < -tile_size.y || tx > texture_limits.x || ty > texture_limits.y {
-                continue;
-            }
+/// Draws each tile as its connections, coloured by their hashes
+pub struct DebugRenderer;
 
-            let tile = &grid.tilegrid[i as usize][j as usize];
-            // alternate choice methods
-            // let tileopt = &tile.possible_tiles[rand as usize % tile.possible_tiles.len()];
-            // let tileopt = &tile.possible_tiles[0];
-            let tileopt = if tile.possible_tiles.len() == 1 {&tile.possible_tiles[0]}
-            else {tile.possible_tiles.choose(&mut ::rand::thread_rng()).unwrap()};
-            draw_tile_opt(tx, ty, tile_size, tileopt, textures);
-        }
+impl Renderer for DebugRenderer {
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2) {
+        let tile = &grid.tilegrid[i as usize][j as usize];
+        // alternate choice methods
+        // let tileopt = &tile.possible_tiles[rand as usize % tile.possible_tiles.len()];
+        // let tileopt = &tile.possible_tiles[0];
+        let tileopt = if tile.possible_tiles.len() == 1 {&tile.possible_tiles[0]}
+        else {tile.possible_tiles.choose(&mut ::rand::thread_rng()).unwrap()};
+        draw_tile_opt(x, y, tile_size, tileopt);
     }
-    draw_rectangle(grid.width as f32 * tile_size.x, 0.0, grid.width as f32 * tile_size.x, grid.height as f32 * tile_size.y * 2.0, BLACK);
-    draw_rectangle(0.0, grid.height as f32 * tile_size.y, grid.width as f32 * tile_size.x, grid.height as f32 * tile_size.y, BLACK);
 }
 
-fn draw_tile_opt (x: f32, y: f32, tile_size: Vec2, tileopt: &TileChoice, textures: &HashMap<&str, Texture2D>) {
+fn draw_tile_opt (x: f32, y: f32, tile_size: Vec2, tileopt: &TileChoice) {
     for k in 0..4 {
         let connection = tileopt.connections[k];
         let mut hasher = DefaultHasher::new();
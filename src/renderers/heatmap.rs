@@ -0,0 +1,95 @@
+use crate::*;
+use crate::renderers::Renderer;
+
+/// What the heatmap colours tiles by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMode {
+    /// The number of options left
+    Options,
+    /// The Shannon entropy of the options left, which accounts for their weights
+    Entropy,
+}
+
+/// Colours each tile by how undecided it is, from blue for nearly decided to red for completely open.
+/// Decided tiles are grey
+pub struct HeatmapRenderer {
+    pub mode: HeatmapMode,
+    /// The options and entropy of a blank tile, the most a tile can have
+    max_options: usize,
+    max_entropy: f32,
+}
+
+impl HeatmapRenderer {
+    pub fn new (mode: HeatmapMode) -> Self {
+        let blank = UndecidedTile::new();
+        return Self {
+            mode,
+            max_options: blank.possible_tiles.len(),
+            max_entropy: blank.entropy(),
+        };
+    }
+
+    /// How undecided a tile is, from 0 for decided to 1 for a blank tile
+    pub fn heat (&self, tile: &UndecidedTile) -> f32 {
+        let heat = match self.mode {
+            HeatmapMode::Options => (tile.possible_tiles.len() as f32 - 1.0) / max(1, self.max_options - 1) as f32,
+            HeatmapMode::Entropy => if self.max_entropy > 0.0 {tile.entropy() / self.max_entropy} else {0.0},
+        };
+        return heat.clamp(0.0, 1.0);
+    }
+
+    /// The lowest and highest values the legend goes between, as text
+    fn range_labels (&self) -> (String, String) {
+        return match self.mode {
+            HeatmapMode::Options => ("1".to_string(), format!("{}", self.max_options)),
+            HeatmapMode::Entropy => ("0".to_string(), format!("{:.2}", self.max_entropy)),
+        };
+    }
+
+    /// Draws a key for the colours in the top right corner of the screen, under the solver status
+    pub fn draw_legend (&self) {
+        const WIDTH: f32 = 160.0;
+        const HEIGHT: f32 = 50.0;
+        const BAR_HEIGHT: f32 = 10.0;
+        const STEPS: i32 = 32;
+        let left = screen_width() - WIDTH;
+        let top = 24.0;
+        draw_rectangle(left, top, WIDTH, HEIGHT, BLACK);
+        let title = match self.mode {
+            HeatmapMode::Options => "Options left",
+            HeatmapMode::Entropy => "Entropy",
+        };
+        draw_text(title, left + 5.0, top + 14.0, 16.0, WHITE);
+        let bar_width = WIDTH - 10.0;
+        draw_rectangle(left + 5.0, top + 20.0, bar_width / STEPS as f32, BAR_HEIGHT, heat_color(-1.0));
+        for step in 1..STEPS {
+            let x = left + 5.0 + bar_width * step as f32 / STEPS as f32;
+            draw_rectangle(x, top + 20.0, bar_width / STEPS as f32 + 1.0, BAR_HEIGHT, heat_color(step as f32 / (STEPS - 1) as f32));
+        }
+        let (low, high) = self.range_labels();
+        draw_text(&low, left + 5.0, top + 44.0, 14.0, WHITE);
+        let high_width = measure_text(&high, None, 14, 1.0).width;
+        draw_text(&high, left + WIDTH - 5.0 - high_width, top + 44.0, 14.0, WHITE);
+    }
+}
+
+/// The colour for a heat from 0 to 1, going blue, green, yellow, red. Anything below 0 is decided, and grey
+pub fn heat_color (heat: f32) -> Color {
+    if heat < 0.0 {
+        return DARKGRAY;
+    }
+    let stops = [BLUE, GREEN, YELLOW, RED];
+    let scaled = heat.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let index = min(scaled as usize, stops.len() - 2);
+    let t = scaled - index as f32;
+    let (a, b) = (stops[index], stops[index + 1]);
+    return Color::new(a.r + (b.r - a.r) * t, a.g + (b.g - a.g) * t, a.b + (b.b - a.b) * t, 1.0);
+}
+
+impl Renderer for HeatmapRenderer {
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2) {
+        let tile = &grid.tilegrid[i as usize][j as usize];
+        let color = if tile.possible_tiles.len() <= 1 {heat_color(-1.0)} else {heat_color(self.heat(tile))};
+        draw_rectangle(x, y, tile_size.x, tile_size.y, color);
+    }
+}
@@ -0,0 +1,341 @@
+use crate::*;
+use crate::renderers::atlas::{Atlas, load_missing_image};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The name of the manifest inside each tileset folder
+pub const MANIFEST_NAME: &str = "textures.txt";
+/// How often the watcher looks at the files, in seconds
+const POLL_INTERVAL: f64 = 0.5;
+
+/// A single image cut into a grid of equally sized tiles, numbered left to right then top to bottom from 0
+#[derive(Clone, Debug)]
+pub struct SpriteSheet {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Pixels around the outside of the sheet before the first tile
+    pub margin: u32,
+    /// Pixels between each tile
+    pub spacing: u32,
+}
+
+/// Where a texture's image comes from
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+    Image(PathBuf),
+    /// A tile on one of the manifest's sheets
+    SheetTile {sheet: usize, index: usize},
+}
+
+/// The textures in a tileset folder, read from its textures.txt. Lines starting with # are comments, and the rest are
+/// - `<key> = <path>` for a texture in its own image, with the path relative to the folder
+/// - `sheet <name> = <path> <tile width> <tile height> [margin] [spacing]` for a sprite sheet, whose tiles can be used as `<name>#<index>`
+/// - `<key> = <sheet name>#<index>` to give a tile on a sheet its own name
+#[derive(Clone, Debug)]
+pub struct TextureManifest {
+    pub folder: PathBuf,
+    pub entries: Vec<(&'static str, TextureSource)>,
+    pub sheets: Vec<SpriteSheet>,
+}
+
+/// Splits a key like `castle#12` into its sheet name and index
+pub fn parse_sheet_key (key: &str) -> Option<(&str, usize)> {
+    let (sheet, index) = key.rsplit_once('#')?;
+    return Some((sheet.trim(), index.trim().parse().ok()?));
+}
+
+impl SpriteSheet {
+    /// Reads the part of a sheet line after `sheet`, or why it can't be read
+    fn parse (line: &str, folder: &Path) -> Result<Self, String> {
+        let usage = "a sheet has to be `sheet <name> = <path> <tile width> <tile height> [margin] [spacing]`";
+        let (name, rest) = line.split_once('=').ok_or(usage)?;
+        // The path can have spaces in it, so the numbers are read from the end
+        let mut words: Vec<&str> = rest.split_whitespace().collect();
+        let mut numbers = Vec::new();
+        while numbers.len() < 4 {
+            match words.last().and_then(|word| word.parse::<u32>().ok()) {
+                Some(number) => {
+                    numbers.insert(0, number);
+                    words.pop();
+                }
+                None => break,
+            }
+        }
+        if numbers.len() < 2 || words.is_empty() {
+            return Err(usage.to_string());
+        }
+        // A sheet of empty tiles would have endlessly many of them
+        if numbers[0] == 0 || numbers[1] == 0 {
+            return Err(format!("the sheet's tiles are {}x{}, they can't be 0 pixels wide or high", numbers[0], numbers[1]));
+        }
+        return Ok(Self {
+            name: tileset::intern(name.trim()),
+            path: folder.join(words.join(" ")),
+            tile_width: numbers[0],
+            tile_height: numbers[1],
+            margin: numbers.get(2).copied().unwrap_or(0),
+            spacing: numbers.get(3).copied().unwrap_or(0),
+        });
+    }
+
+    /// How many tiles fit across and down an image of this size
+    pub fn grid_size (&self, width: u32, height: u32) -> (u32, u32) {
+        let across = (width + self.spacing).saturating_sub(2 * self.margin) / (self.tile_width + self.spacing);
+        let down = (height + self.spacing).saturating_sub(2 * self.margin) / (self.tile_height + self.spacing);
+        return (across, down);
+    }
+
+    /// Cuts the sheet's image into its tiles, in index order
+    pub fn slice (&self, image: &Image) -> Vec<Image> {
+        let (across, down) = self.grid_size(image.width() as u32, image.height() as u32);
+        let mut tiles = Vec::new();
+        for row in 0..down {
+            for column in 0..across {
+                let x = self.margin + column * (self.tile_width + self.spacing);
+                let y = self.margin + row * (self.tile_height + self.spacing);
+                tiles.push(image.sub_image(Rect::new(x as f32, y as f32, self.tile_width as f32, self.tile_height as f32)));
+            }
+        }
+        return tiles;
+    }
+
+    /// The number of tiles on the sheet, read straight from the file so it works without a window
+    pub fn tile_count (&self) -> Result<usize, WfcError> {
+        let missing = || WfcError::MissingTexture {path: self.path.display().to_string()};
+        let bytes = fs::read(&self.path).map_err(|_| missing())?;
+        let image = Image::from_file_with_format(&bytes, None).map_err(|_| missing())?;
+        let (across, down) = self.grid_size(image.width() as u32, image.height() as u32);
+        return Ok((across * down) as usize);
+    }
+}
+
+impl TextureManifest {
+    pub fn load (folder: &str) -> Result<Self, WfcError> {
+        let folder = PathBuf::from(folder);
+        let path = folder.join(MANIFEST_NAME);
+        let text = fs::read_to_string(&path).map_err(|_| WfcError::MissingTexture {path: path.display().to_string()})?;
+        let mut manifest = Self {folder: folder.clone(), entries: Vec::new(), sheets: Vec::new()};
+        // Sheets can be used before the line that defines them, so tiles on them are looked up afterwards
+        let mut sheet_tiles = Vec::new();
+        for (number, line) in text.lines().map(str::trim).enumerate().map(|(i, line)| (i + 1, line)) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(sheet) = line.strip_prefix("sheet ") {
+                match SpriteSheet::parse(sheet, &folder) {
+                    Ok(sheet) => manifest.sheets.push(sheet),
+                    Err(reason) => println!("Couldn't read line {} of {}, {}", number, path.display(), reason),
+                }
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, source)) => match parse_sheet_key(source) {
+                    Some((sheet, index)) => sheet_tiles.push((tileset::intern(key.trim()), sheet.to_string(), index)),
+                    None => manifest.entries.push((tileset::intern(key.trim()), TextureSource::Image(folder.join(source.trim())))),
+                },
+                None => println!("Couldn't read line {} of {}, \"{}\" isn't `<key> = <path>`", number, path.display(), line),
+            }
+        }
+        for (key, sheet, index) in sheet_tiles {
+            match manifest.sheets.iter().position(|defined| defined.name == sheet) {
+                Some(sheet) => manifest.entries.push((key, TextureSource::SheetTile {sheet, index})),
+                None => println!("There's no sheet called \"{}\" in {}", sheet, path.display()),
+            }
+        }
+        return Ok(manifest);
+    }
+
+    /// Where the manifest itself is
+    pub fn path (&self) -> PathBuf {
+        return self.folder.join(MANIFEST_NAME);
+    }
+
+    /// Every file the textures come from, including the manifest
+    pub fn files (&self) -> Vec<PathBuf> {
+        let mut files = vec![self.path()];
+        for (_, source) in self.entries.iter() {
+            if let TextureSource::Image(path) = source {
+                files.push(path.clone());
+            }
+        }
+        files.extend(self.sheets.iter().map(|sheet| sheet.path.clone()));
+        return files;
+    }
+
+    /// Where a key's image comes from, either a line of its own or `<sheet>#<index>`
+    pub fn source (&self, key: &str) -> Option<TextureSource> {
+        if let Some((_, source)) = self.entries.iter().find(|(entry, _)| *entry == key) {
+            return Some(source.clone());
+        }
+        let (name, index) = parse_sheet_key(key)?;
+        let sheet = self.sheets.iter().position(|sheet| sheet.name == name)?;
+        return Some(TextureSource::SheetTile {sheet, index});
+    }
+
+    /// Loads every image in the manifest into an atlas, along with every tile on every sheet as `<sheet>#<index>`.
+    /// Anything that couldn't be loaded is drawn with missing.png and returned as an error
+    pub async fn load_atlas (&self) -> (Atlas, Vec<WfcError>) {
+        let mut errors = Vec::new();
+        let missing_image = match load_missing_image().await {
+            Ok(image) => image,
+            Err((image, error)) => {
+                errors.push(error);
+                image
+            }
+        };
+
+        let mut sliced = Vec::new();
+        for sheet in self.sheets.iter() {
+            match load_image(&sheet.path.display().to_string()).await {
+                Ok(image) => sliced.push(sheet.slice(&image)),
+                Err(_) => {
+                    println!("Failed to load sprite sheet from path: {}", sheet.path.display());
+                    errors.push(WfcError::MissingTexture {path: sheet.path.display().to_string()});
+                    sliced.push(Vec::new());
+                }
+            }
+        }
+
+        let mut images = Vec::new();
+        for (key, source) in self.entries.iter() {
+            match source {
+                TextureSource::Image(path) => match load_image(&path.display().to_string()).await {
+                    Ok(image) => images.push((*key, image)),
+                    Err(_) => {
+                        println!("Failed to load texture from path: {}", path.display());
+                        errors.push(WfcError::MissingTexture {path: path.display().to_string()});
+                    }
+                },
+                TextureSource::SheetTile {sheet, index} => match sliced[*sheet].get(*index) {
+                    Some(image) => images.push((*key, image.clone())),
+                    None if !sliced[*sheet].is_empty() => {
+                        println!("{} has no tile {}", self.sheets[*sheet].name, index);
+                        errors.push(WfcError::MissingTexture {path: format!("{}#{}", self.sheets[*sheet].name, index)});
+                    }
+                    None => {}
+                },
+            }
+        }
+        for (sheet, tiles) in self.sheets.iter().zip(sliced) {
+            for (index, image) in tiles.into_iter().enumerate() {
+                images.push((tileset::intern(&format!("{}#{}", sheet.name, index)), image));
+            }
+        }
+        return (Atlas::new(&images, &missing_image), errors);
+    }
+}
+
+/// Notices when the manifest or any image in it changes on disk, by checking when they were last modified every so often.
+/// Changes are only reported once the files have stopped changing, so a half written image isn't loaded
+pub struct TextureWatcher {
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+    last_poll: f64,
+    /// Whether something changed at the last poll, reported once a poll sees nothing new
+    pending: bool,
+}
+
+impl TextureWatcher {
+    pub fn new (manifest: &TextureManifest) -> Self {
+        let mut watcher = Self {
+            modified: HashMap::new(),
+            last_poll: get_time(),
+            pending: false,
+        };
+        watcher.check(manifest);
+        watcher.pending = false;
+        return watcher;
+    }
+
+    /// Returns true when the textures should be reloaded
+    pub fn poll (&mut self, manifest: &TextureManifest) -> bool {
+        if get_time() - self.last_poll < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = get_time();
+        let changed = self.check(manifest);
+        if changed {
+            self.pending = true;
+            return false;
+        }
+        return std::mem::take(&mut self.pending);
+    }
+
+    /// Records when each file was last modified, returning true if any of them are different to last time
+    fn check (&mut self, manifest: &TextureManifest) -> bool {
+        let mut changed = false;
+        for path in manifest.files() {
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+            if self.modified.insert(path, modified) != Some(modified) {
+                changed = true;
+            }
+        }
+        return changed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image where each pixel's red and green are its x and y
+    fn numbered_image (width: u16, height: u16) -> Image {
+        let mut image = Image::gen_image_color(width, height, BLACK);
+        for x in 0..width as u32 {
+            for y in 0..height as u32 {
+                image.set_pixel(x, y, Color::from_rgba(x as u8, y as u8, 0, 255));
+            }
+        }
+        return image;
+    }
+
+    #[test]
+    fn sheet_lines_are_read_from_the_end_so_paths_can_have_spaces () {
+        let sheet = SpriteSheet::parse("tiles = my tiles.png 16 8 2 1", Path::new("assets")).unwrap();
+        assert_eq!(sheet.name, "tiles");
+        assert_eq!(sheet.path, Path::new("assets").join("my tiles.png"));
+        assert_eq!((sheet.tile_width, sheet.tile_height, sheet.margin, sheet.spacing), (16, 8, 2, 1));
+
+        let sheet = SpriteSheet::parse("tiles = tiles.png 16 16", Path::new("assets")).unwrap();
+        assert_eq!((sheet.margin, sheet.spacing), (0, 0));
+    }
+
+    #[test]
+    fn sheet_lines_without_a_size_or_with_empty_tiles_are_rejected () {
+        assert!(SpriteSheet::parse("tiles = tiles.png 16", Path::new("assets")).is_err());
+        assert!(SpriteSheet::parse("tiles.png 16 16", Path::new("assets")).is_err());
+        assert!(SpriteSheet::parse("tiles = 16 16", Path::new("assets")).is_err());
+        assert!(SpriteSheet::parse("tiles = tiles.png 0 16", Path::new("assets")).is_err());
+        assert!(SpriteSheet::parse("tiles = tiles.png 16 0 1 1", Path::new("assets")).is_err());
+    }
+
+    #[test]
+    fn sheets_are_sliced_around_their_margin_and_spacing () {
+        let sheet = SpriteSheet::parse("tiles = tiles.png 4 3 2 1", Path::new("assets")).unwrap();
+        // 2 + 4 + 1 + 4 + 2 across, with 2 pixels left over that don't fit a tile, and 2 + 3 + 1 + 3 + 2 down
+        let image = numbered_image(15, 11);
+        assert_eq!(sheet.grid_size(15, 11), (2, 2));
+        let tiles = sheet.slice(&image);
+        assert_eq!(tiles.len(), 4);
+        // Tiles go left to right then top to bottom, so each one's top left is at its place in the grid
+        for (tile, (x, y)) in tiles.iter().zip([(2, 2), (7, 2), (2, 6), (7, 6)]) {
+            assert_eq!((tile.width(), tile.height()), (4, 3));
+            assert_eq!(tile.get_pixel(0, 0), image.get_pixel(x, y));
+        }
+        // An index past the last tile isn't on the sheet
+        assert!(tiles.get(4).is_none());
+        assert_eq!(sheet.grid_size(0, 0), (0, 0));
+    }
+
+    #[test]
+    fn sheet_tiles_are_counted_from_the_file () {
+        // castle.png is 256x256, so it's 16 tiles across and down
+        let sheet = SpriteSheet::parse("castle = castle.png 16 16", Path::new("assets/castle")).unwrap();
+        assert_eq!(sheet.tile_count(), Ok(256));
+        let sheet = SpriteSheet::parse("castle = missing.png 16 16", Path::new("assets/castle")).unwrap();
+        assert!(sheet.tile_count().is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::*;
+use crate::renderers::{Renderer, visible_tiles, with_variant};
+use crate::renderers::atlas::Atlas;
+use crate::renderers::batch::SpriteBatch;
+
+/// How dark a tile with every option still possible is drawn, from 0 to 1
+const MAX_FADE: f32 = 0.8;
+
+/// Draws each tile as the weighted average of all its remaining options, faded towards black the more uncertain it is,
+/// so the grid can be seen settling as it collapses
+pub struct SuperpositionRenderer {
+    pub atlas: Atlas,
+    /// The entropy of a blank tile, the most uncertain a tile can be
+    max_entropy: f32,
+}
+
+impl SuperpositionRenderer {
+    pub fn new (atlas: Atlas) -> Self {
+        return Self {
+            atlas,
+            max_entropy: UndecidedTile::new().entropy(),
+        };
+    }
+
+    /// Adds every option of a tile to the batch, each one partly covering the ones before
+    fn push_tile (&self, batch: &mut SpriteBatch, tileset: &Tileset, grid: &TileGrid, i: i32, j: i32, tile_size: Vec2) {
+        let tile = &grid.tilegrid[i as usize][j as usize];
+        let (x, y) = (i as f32 * tile_size.x, j as f32 * tile_size.y);
+        // Drawing the kth option with alpha w_k / (w_1 + ... + w_k) leaves each option making up w_k / W of the result
+        let mut total = 0.0;
+        for tileopt in tile.possible_tiles.iter() {
+            let weight = max(tileopt.weight, 0) as f32;
+            total += weight;
+            let alpha = if total > 0.0 {weight / total} else {1.0};
+            batch.push_tinted(x, y, tile_size, &with_variant(tileset, grid, i, j, tileopt), Color::new(1.0, 1.0, 1.0, alpha));
+        }
+    }
+
+    /// Darkens a tile depending on how many options it has left
+    fn draw_fade (&self, grid: &TileGrid, i: i32, j: i32, tile_size: Vec2) {
+        if self.max_entropy <= 0.0 {
+            return;
+        }
+        let entropy = grid.tilegrid[i as usize][j as usize].entropy();
+        let fade = (entropy / self.max_entropy).clamp(0.0, 1.0) * MAX_FADE;
+        if fade > 0.0 {
+            draw_rectangle(i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size.x, tile_size.y, Color::new(0.0, 0.0, 0.0, fade));
+        }
+    }
+}
+
+impl Renderer for SuperpositionRenderer {
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2) {
+        let mut batch = SpriteBatch::new(&self.atlas);
+        self.push_tile(&mut batch, &tileset::current(), grid, i, j, tile_size);
+        batch.flush();
+        self.draw_fade(grid, i, j, tile_size);
+    }
+
+    fn draw_region (&self, grid: &TileGrid, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        let tileset = tileset::current();
+        let mut batch = SpriteBatch::new(&self.atlas);
+        for i in left..right {
+            for j in top..bottom {
+                self.push_tile(&mut batch, &tileset, grid, i, j, tile_size);
+            }
+        }
+        // The fades go on top of every tile, so the batch has to be drawn first
+        batch.flush();
+        for i in left..right {
+            for j in top..bottom {
+                self.draw_fade(grid, i, j, tile_size);
+            }
+        }
+    }
+
+    fn draw_tiles (&self, grid: &TileGrid, tiles: &HashSet<(i32, i32)>, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        let tiles: Vec<(i32, i32)> = tiles.iter().copied().filter(|&(i, j)| i >= left && i < right && j >= top && j < bottom).collect();
+        // The options are blended over whatever is already there, so what was drawn before has to be cleared first
+        for &(i, j) in tiles.iter() {
+            draw_rectangle(i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size.x, tile_size.y, BLACK);
+        }
+        let tileset = tileset::current();
+        let mut batch = SpriteBatch::new(&self.atlas);
+        for &(i, j) in tiles.iter() {
+            self.push_tile(&mut batch, &tileset, grid, i, j, tile_size);
+        }
+        batch.flush();
+        for &(i, j) in tiles.iter() {
+            self.draw_fade(grid, i, j, tile_size);
+        }
+    }
+}
@@ -1,42 +1,20 @@
 use crate::*;
+use crate::renderers::{Renderer, visible_tiles, with_variant, source_rect};
+use crate::renderers::atlas::{Atlas, load_missing_image};
+use crate::renderers::batch::SpriteBatch;
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::fs;
 
-/// Loads all textures from a folder, assigning each one to a key.
-/// Mainly for example, since there's no guarantee the correct files will be associated with the keys you want
-
-pub async fn load_textures_unordered<T: Hash + Eq + Clone>(folder: &str, keys: &[T]) -> HashMap<T, Texture2D> {
-
-    let mut hashmap = HashMap::new();
-    let missing_texture = load_texture("missing.png").await.unwrap();
-    let paths = fs::read_dir(folder);
-
-    if let Ok(paths) = paths {
-        for (i, dir) in paths.enumerate() {
-            if keys.len() <= i {
-                break;
-            }
-            if let Ok(dir) = dir {
-                let texture = load_texture(dir.path().to_str().unwrap()).await;
-                if let Ok(texture) = texture {
-                    hashmap.insert(keys[i].clone(), texture);
-                }
-                else {
-                    hashmap.insert(keys[i].clone(), missing_texture.clone());
-                }
-            }
-            else {
-                hashmap.insert(keys[i].clone(), missing_texture.clone());
-            }
+/// missing.png as a texture, or a generated checkerboard if it can't be loaded
+async fn load_missing_texture () -> Texture2D {
+    let image = match load_missing_image().await {
+        Ok(image) => image,
+        Err((image, error)) => {
+            println!("{}", error);
+            image
         }
-    }
-    else {
-        for key in keys {
-            hashmap.insert(key.clone(), missing_texture.clone());
-        }
-    }
-    return hashmap;
+    };
+    return Texture2D::from_image(&image);
 }
 
 /// Load textures from a folder (not ending in /), using the associated paths as filenames.
@@ -48,7 +26,7 @@ pub async fn load_textures_unordered<T: Hash + Eq + Clone>(folder: &str, keys: &
 pub async fn load_textures_paths<T: Hash + Eq + Clone>(paths: &[&str], keys: &[T]) -> HashMap<T, Texture2D> {
 
     let mut hashmap = HashMap::new();
-    let missing_texture = load_texture("missing.png").await.unwrap();
+    let missing_texture = load_missing_texture().await;
 
     for i in 0..paths.len() {
         if keys.len() <= i {
@@ -83,7 +61,7 @@ pub async fn load_textures_paths<T: Hash + Eq + Clone>(paths: &[&str], keys: &[T
 pub async fn load_textures_stringable<T: Hash + Eq + Clone + ToString>(folder: &str, keys: &[T]) -> HashMap<T, Texture2D> {
 
     let mut hashmap = HashMap::new();
-    let missing_texture = load_texture("missing.png").await.unwrap();
+    let missing_texture = load_missing_texture().await;
 
     for key in keys {
         let texture = load_texture(
@@ -100,45 +78,128 @@ pub async fn load_textures_stringable<T: Hash + Eq + Clone + ToString>(folder: &
     return hashmap;
 }
 
-pub fn draw_tilegrid (grid: &TileGrid, textures: &HashMap<&str, Texture2D>, tile_size: Vec2, texture_limits: Vec2, offset: i32, render_every: i32) {
-    for i in 0..grid.width {
-        for j in 0..grid.height {
-            // render 1 in render_every tiles
-            if (i*101 + j*5)%render_every != offset%render_every {
-                continue;
+/// Draws each tile using its texture from the atlas, picking a random option for undecided tiles.
+/// Big tiles that are fully placed are drawn as one image, and any part of one on its own is drawn with its share of the image.
+/// Layers are drawn on top, in order
+pub struct TextureRenderer {
+    pub atlas: Atlas,
+}
+
+impl Renderer for TextureRenderer {
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2) {
+        let tileset = tileset::current();
+        draw_tile_opt(x, y, tile_size, &shown_option(&tileset, grid, i, j), &self.atlas);
+        for layer in grid.layers.iter() {
+            if let Some(tileopt) = layer_option(&tileset, layer, i, j) {
+                draw_tile_opt(x, y, tile_size, &tileopt, &self.atlas);
             }
-            let tx = (i as f32) * tile_size.x;
-            let ty = (j as f32) * tile_size.y;
-            // don't render tiles that are offscreen
-            
-            if tx < -tile_size.x || ty < -tile_size.y || tx > texture_limits.x || ty > texture_limits.y {
+        }
+    }
+
+    fn draw_region (&self, grid: &TileGrid, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        let tileset = tileset::current();
+        let mut batch = SpriteBatch::new(&self.atlas);
+        let mut drawn = HashSet::new();
+        for i in left..right {
+            for j in top..bottom {
+                push_tile(&mut batch, &tileset, grid, i, j, tile_size, &mut drawn);
+            }
+        }
+        // Each layer goes over the whole of the one below it
+        for layer in grid.layers.iter() {
+            for i in left..right {
+                for j in top..bottom {
+                    if let Some(tileopt) = layer_option(&tileset, layer, i, j) {
+                        batch.push(i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size, &tileopt);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_tiles (&self, grid: &TileGrid, tiles: &HashSet<(i32, i32)>, view_rect: Rect, tile_size: Vec2) {
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        let tileset = tileset::current();
+        let mut batch = SpriteBatch::new(&self.atlas);
+        let mut drawn = HashSet::new();
+        for &(i, j) in tiles.iter() {
+            if i < left || i >= right || j < top || j >= bottom {
                 continue;
             }
-            let tile = &grid.tilegrid[i as usize][j as usize];
-            // alternate choice methods
-            // let tileopt = &tile.possible_tiles[rand as usize % tile.possible_tiles.len()];
-            // let tileopt = &tile.possible_tiles[0];
-            let tileopt = if tile.possible_tiles.len() == 1 {&tile.possible_tiles[0]}
-            else {tile.possible_tiles.choose(&mut ::rand::thread_rng()).unwrap()};
+            push_tile(&mut batch, &tileset, grid, i, j, tile_size, &mut drawn);
+        }
+        for layer in grid.layers.iter() {
+            for &(i, j) in tiles.iter() {
+                if i < left || i >= right || j < top || j >= bottom {
+                    continue;
+                }
+                if let Some(tileopt) = layer_option(&tileset, layer, i, j) {
+                    batch.push(i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size, &tileopt);
+                }
+            }
+        }
+    }
+}
+
+/// The option to show for a tile, which is a random one if it's undecided, drawn with its variant for that position
+fn shown_option (tileset: &Tileset, grid: &TileGrid, i: i32, j: i32) -> TileChoice {
+    let tile = &grid.tilegrid[i as usize][j as usize];
+    // alternate choice methods
+    // let tileopt = &tile.possible_tiles[rand as usize % tile.possible_tiles.len()];
+    // let tileopt = &tile.possible_tiles[0];
+    let tileopt = if tile.possible_tiles.len() == 1 {&tile.possible_tiles[0]}
+    else {tile.possible_tiles.choose(&mut ::rand::thread_rng()).unwrap()};
+    return with_variant(tileset, grid, i, j, tileopt);
+}
 
-            draw_tile_opt(tx, ty, tile_size, tileopt, textures);
+/// The option to show for a tile on a layer, which is only drawn once it's decided so the layer below shows through until then
+fn layer_option (tileset: &Tileset, layer: &TileGrid, i: i32, j: i32) -> Option<TileChoice> {
+    let tile = &layer.tilegrid[i as usize][j as usize];
+    if tile.possible_tiles.len() != 1 {
+        return None;
+    }
+    return Some(with_variant(tileset, layer, i, j, &tile.possible_tiles[0]));
+}
+
+/// Adds tile (i, j) to the batch, or the whole big tile it's part of if every part of it is placed.
+/// drawn is the top left cell of each big tile already added, so a big tile is only drawn once
+fn push_tile (batch: &mut SpriteBatch, tileset: &Tileset, grid: &TileGrid, i: i32, j: i32, tile_size: Vec2, drawn: &mut HashSet<(i32, i32)>) {
+    let tile = &grid.tilegrid[i as usize][j as usize];
+    if tile.possible_tiles.len() == 1 {
+        if let Some(part) = tileset.part(tile.possible_tiles[0].texture) {
+            let cells = tileset.big_tile_cells(i, j, tile.possible_tiles[0]);
+            let placed = cells.iter().all(|&(x, y, part)| {
+                x >= 0 && x < grid.width && y >= 0 && y < grid.height && grid.tilegrid[x as usize][y as usize].possible_tiles == [part]
+            });
+            if placed {
+                let (x, y) = (i - part.x, j - part.y);
+                if drawn.insert((x, y)) {
+                    let big = &tileset.big_tiles[part.big];
+                    let size = Vec2::new(tile_size.x * big.width as f32, tile_size.y * big.height as f32);
+                    batch.push_big(x as f32 * tile_size.x, y as f32 * tile_size.y, size, big);
+                }
+                return;
+            }
         }
     }
-    draw_rectangle(grid.width as f32 * tile_size.x, 0.0, grid.width as f32 * tile_size.x, grid.height as f32 * tile_size.y * 2.0, BLACK);
-    draw_rectangle(0.0, grid.height as f32 * tile_size.y, grid.width as f32 * tile_size.x, grid.height as f32 * tile_size.y, BLACK);
+    batch.push(i as f32 * tile_size.x, j as f32 * tile_size.y, tile_size, &shown_option(tileset, grid, i, j));
 }
 
-pub fn draw_tile_opt (x: f32, y: f32, tile_size: Vec2, tileopt: &TileChoice, textures: &HashMap<&str, Texture2D>) {
-    let texture = textures.get(tileopt.texture).unwrap();
+/// Draws a single tile on its own, for when there aren't enough tiles to be worth batching, like the hover panel.
+/// Textures with variants are drawn as their first one, so pass the option through with_variant first to draw it as it is on the grid
+pub fn draw_tile_opt (x: f32, y: f32, tile_size: Vec2, tileopt: &TileChoice, atlas: &Atlas) {
+    let tileset = tileset::current();
     let dest_size = if tileopt.rot90 {Vec2::new(tile_size.y, tile_size.x)} else {tile_size};
     // textures are rotated at their center after scaling, which won't be the same as the tile's center
     let x = x + if tileopt.rot90 {(tile_size.x-tile_size.y)/2.0} else {0.0};
     let y = y + if tileopt.rot90 {(tile_size.y-tile_size.x)/2.0} else {0.0};
     let params = DrawTextureParams {
         dest_size: Some(dest_size),
+        source: Some(source_rect(&tileset, atlas, tileset.preview(tileopt.texture))),
         rotation: if tileopt.rot90 {std::f32::consts::FRAC_PI_2} else {0.0},
         flip_x: tileopt.flipx,
         flip_y: tileopt.flipy,
         ..Default::default()};
-    draw_texture_ex(texture, x, y, WHITE, params);
+    draw_texture_ex(&atlas.texture, x, y, WHITE, params);
 }
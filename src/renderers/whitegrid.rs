@@ -1,15 +1,26 @@
 use crate::*;
+use crate::renderers::{Renderer, visible_tiles};
 
-pub fn draw_tilegrid (grid: &TileGrid, textures: &HashMap<&str, Texture2D>, tile_size: Vec2, texture_limits: Vec2, offset: i32, render_every: i32) {
-    for i in 0..grid.height+1 {
-        draw_line(0.0, (i as f32) * tile_size.y,
-                  grid.width as f32*tile_size.x, (i as f32) * tile_size.y,
-                  1.0, WHITE);
-    }
-    for i in 0..grid.width+1 {
-        draw_line((i as f32) * tile_size.x, 0.0,
-                  (i as f32) * tile_size.x, grid.height as f32*tile_size.y, 
-                  1.0, WHITE);
+/// Draws white lines between the tiles
+pub struct WhitegridRenderer;
+
+impl Renderer for WhitegridRenderer {
+    fn draw_tile (&self, grid: &TileGrid, i: i32, j: i32, x: f32, y: f32, tile_size: Vec2) {
+        draw_rectangle_lines(x, y, tile_size.x, tile_size.y, 1.0, WHITE);
     }
 
+    fn draw_region (&self, grid: &TileGrid, view_rect: Rect, tile_size: Vec2) {
+        // Whole lines are cheaper than an outline for each tile
+        let (left, top, right, bottom) = visible_tiles(grid, view_rect, tile_size);
+        for i in top..bottom+1 {
+            draw_line(left as f32 * tile_size.x, (i as f32) * tile_size.y,
+                      right as f32 * tile_size.x, (i as f32) * tile_size.y,
+                      1.0, WHITE);
+        }
+        for i in left..right+1 {
+            draw_line((i as f32) * tile_size.x, top as f32 * tile_size.y,
+                      (i as f32) * tile_size.x, bottom as f32 * tile_size.y,
+                      1.0, WHITE);
+        }
+    }
 }
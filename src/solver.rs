@@ -0,0 +1,497 @@
+use crate::*;
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// The number of collapses per second the solver starts at, the same as the old 100 collapses per frame at 60fps
+pub const DEFAULT_RATE: u32 = 6000;
+/// The number of collapses the solver remembers how to undo, for backtracking out of contradictions
+const MAX_HISTORY: usize = 1000;
+/// The most collapses per second the rate can be set to, far more than a grid can be collapsed at
+pub const MAX_RATE: u32 = 10_000_000;
+
+/// An edit to make to the solver's grid, run on the solver thread
+type Edit = Box<dyn FnOnce(&mut TileGrid) -> Result<(), WfcError> + Send>;
+
+/// Messages sent from the viewer to the solver thread
+enum Command {
+    Pause,
+    Resume,
+    /// Collapse a single tile, even while paused
+    Step,
+    /// Set the number of collapses per second
+    SetRate(u32),
+    Edit(Edit),
+    /// Start or stop sending which tile will be collapsed next
+    WatchNextPick(bool),
+    /// Undo collapses until the one that led to the last contradiction can be made differently
+    Backtrack,
+    Stop,
+}
+
+/// Messages sent from the solver thread back to the viewer
+enum Event {
+    /// A tile's options changed
+    TileChanged(i32, i32, UndecidedTile),
+    /// The grid changed size, so the whole grid is sent over
+    Replaced(Box<TileGrid>),
+    /// The layers on top of the grid were generated
+    Layers(Vec<TileGrid>),
+    /// An edit finished being applied
+    EditDone,
+    /// The tile that will be collapsed next, if there are any left
+    NextPick(Option<(i32, i32)>),
+    /// There are no undecided tiles left, the solver pauses itself
+    Finished,
+    /// A collapse or edit failed and was undone, the solver pauses itself
+    Failed(Failure),
+}
+
+/// Why the solver stopped, and whether backtracking could get past it
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub error: WfcError,
+    /// Only contradictions from the solver's own collapses can be backtracked out of, edits are just undone
+    pub can_backtrack: bool,
+}
+
+/// A collapse the solver made, and how to undo it
+struct Decision {
+    x: i32,
+    y: i32,
+    chosen: TileChoice,
+    changes: Changes,
+}
+
+/// Runs the collapse on a worker thread which owns the real grid.
+/// The viewer keeps a copy of the grid which is kept up to date by applying the changes the worker sends back,
+/// and every edit is sent to the worker rather than being made to the viewer's copy
+pub struct Solver {
+    commands: Sender<Command>,
+    events: Receiver<Event>,
+    thread: Option<JoinHandle<()>>,
+    /// Whether the solver is paused, as last requested by the viewer
+    pub paused: bool,
+    /// The number of collapses per second
+    pub rate: u32,
+    /// The number of edits that have been sent but not applied yet
+    pub pending_edits: usize,
+    /// The tile that will be collapsed next, only kept up to date while watching it
+    pub next_pick: Option<(i32, i32)>,
+    /// Why the solver last stopped, until it's restarted or backtracked
+    pub failure: Option<Failure>,
+}
+
+impl Solver {
+    /// Starts a paused solver thread working on a copy of the grid
+    pub fn start (grid: TileGrid) -> Self {
+        let (commands, command_receiver) = channel();
+        let (event_sender, events) = channel();
+        let thread = std::thread::spawn(move || run(grid, command_receiver, event_sender));
+        return Self {
+            commands,
+            events,
+            thread: Some(thread),
+            paused: true,
+            rate: DEFAULT_RATE,
+            pending_edits: 0,
+            next_pick: None,
+            failure: None,
+        };
+    }
+
+    pub fn pause (&mut self) {
+        self.paused = true;
+        self.send(Command::Pause);
+    }
+
+    pub fn resume (&mut self) {
+        self.paused = false;
+        self.send(Command::Resume);
+    }
+
+    pub fn step (&mut self) {
+        self.send(Command::Step);
+    }
+
+    /// Sets the number of collapses per second, kept between 1 and MAX_RATE
+    pub fn set_rate (&mut self, rate: u32) {
+        self.rate = rate.clamp(1, MAX_RATE);
+        self.send(Command::SetRate(self.rate));
+    }
+
+    /// Asks the solver to send which tile it will collapse next whenever that changes, which takes an extra pick each step
+    pub fn watch_next_pick (&mut self, watch: bool) {
+        if !watch {
+            self.next_pick = None;
+        }
+        self.send(Command::WatchNextPick(watch));
+    }
+
+    /// Undoes collapses until the one that caused the failure can be made another way, then carries on
+    pub fn backtrack (&mut self) {
+        self.failure = None;
+        self.send(Command::Backtrack);
+        self.resume();
+    }
+
+    /// Throws the grid away for a blank one
+    pub fn restart (&mut self) {
+        self.failure = None;
+        self.edit(|grid| grid.reset());
+    }
+
+    /// Queues an edit to the grid, which will be applied on the solver thread between collapses.
+    /// If the edit fails, whatever it changed is undone
+    pub fn edit (&mut self, edit: impl FnOnce(&mut TileGrid) -> Result<(), WfcError> + Send + 'static) {
+        self.pending_edits += 1;
+        self.send(Command::Edit(Box::new(edit)));
+    }
+
+    /// Applies every change the solver has sent so far to the viewer's copy of the grid
+    pub fn apply_events (&mut self, grid: &mut TileGrid) {
+        let tileset = tileset::current();
+        loop {
+            match self.events.try_recv() {
+                Ok(Event::TileChanged(x, y, tile)) => grid.set_tile(&tileset, x, y, tile),
+                Ok(Event::Replaced(new_grid)) => {
+                    *grid = *new_grid;
+                    grid.mark_all_dirty();
+                }
+                Ok(Event::Layers(layers)) => {
+                    grid.layers = layers;
+                    grid.mark_all_dirty();
+                }
+                Ok(Event::EditDone) => self.pending_edits -= 1,
+                Ok(Event::NextPick(next_pick)) => self.next_pick = next_pick,
+                Ok(Event::Finished) => self.paused = true,
+                Ok(Event::Failed(failure)) => {
+                    self.paused = true;
+                    self.failure = Some(failure);
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    // The solver only stops early if it panicked, so pass the panic on
+                    if let Some(thread) = self.thread.take() {
+                        if let Err(panic) = thread.join() {
+                            std::panic::resume_unwind(panic);
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn send (&self, command: Command) {
+        // If the thread has died, apply_events will report why
+        let _ = self.commands.send(command);
+    }
+}
+
+impl Drop for Solver {
+    fn drop (&mut self) {
+        self.send(Command::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The solver thread's main loop
+fn run (mut grid: TileGrid, commands: Receiver<Command>, events: Sender<Event>) {
+    let mut paused = true;
+    let mut rate = DEFAULT_RATE;
+    // Collapses are spread out over time to match the rate, rather than being done in bursts
+    let mut started = Instant::now();
+    let mut collapsed: u64 = 0;
+    // The last next pick sent to the viewer, None when not watching or nothing has been sent yet
+    let mut watching = false;
+    let mut sent_pick: Option<Option<(i32, i32)>> = None;
+    // Recent collapses, newest last, and the collapse that contradicted if the solver stopped because of one
+    let mut history = VecDeque::<Decision>::new();
+    let mut failed: Option<(i32, i32, TileChoice)> = None;
+    loop {
+        let mut steps = 0;
+        // While paused there is nothing to do until the viewer asks for something, so block
+        let command = if paused {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => return,
+            }
+        }
+        else {
+            match commands.try_recv() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        };
+        match command {
+            Some(Command::Pause) => paused = true,
+            Some(Command::Resume) => {
+                paused = false;
+                started = Instant::now();
+                collapsed = 0;
+            }
+            Some(Command::Step) => steps = 1,
+            Some(Command::SetRate(new_rate)) => {
+                rate = new_rate;
+                started = Instant::now();
+                collapsed = 0;
+            }
+            Some(Command::Edit(edit)) => {
+                let (width, height) = (grid.width, grid.height);
+                let result = edit(&mut grid);
+                // Undoing collapses from before an edit could undo the edit too
+                history.clear();
+                failed = None;
+                if let Err(error) = result {
+                    paused = true;
+                    if !send_failure(&grid, &events, Failure {error, can_backtrack: false}) {
+                        return;
+                    }
+                }
+                if grid.width != width || grid.height != height {
+                    grid.take_dirty();
+                    // The viewer has no use for the trace, so it isn't sent over
+                    let trace = grid.trace.take();
+                    let sent = events.send(Event::Replaced(Box::new(grid.clone())));
+                    grid.trace = trace;
+                    if sent.is_err() {
+                        return;
+                    }
+                }
+                if !send_changes(&mut grid, &events) || events.send(Event::EditDone).is_err() {
+                    return;
+                }
+                if watching && !send_next_pick(&mut grid, &events, &mut sent_pick) {
+                    return;
+                }
+                continue;
+            }
+            Some(Command::WatchNextPick(watch)) => {
+                watching = watch;
+                sent_pick = None;
+            }
+            Some(Command::Backtrack) => {
+                if let Some(choice) = failed.take() {
+                    if let Err(error) = backtrack(&mut grid, &mut history, choice) {
+                        paused = true;
+                        if !send_failure(&grid, &events, Failure {error, can_backtrack: false}) {
+                            return;
+                        }
+                    }
+                }
+            }
+            Some(Command::Stop) => return,
+            None => {}
+        }
+
+        if !paused {
+            let due = (started.elapsed().as_secs_f64() * rate as f64) as u64;
+            if due <= collapsed {
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+            // Do at most a hundredth of a second's worth at a time so commands are still handled quickly,
+            // and drop anything more than a second behind rather than trying to catch up
+            steps = min(due - collapsed, max(1, rate as u64 / 100));
+            collapsed = max(collapsed + steps, due.saturating_sub(rate as u64));
+        }
+        for _ in 0..steps {
+            match next_index(&mut grid) {
+                Some((x, y)) => if let Err(error) = collapse(&mut grid, &mut history, &mut failed, x, y) {
+                    paused = true;
+                    if !send_failure(&grid, &events, Failure {error, can_backtrack: failed.is_some()}) {
+                        return;
+                    }
+                    break;
+                }
+                None => {
+                    paused = true;
+                    // Every tile is decided, so the layers on top can be generated. The changes that decided the last tiles go first,
+                    // since they take off whatever was on top of them
+                    if grid.layers_pending() {
+                        if !send_changes(&mut grid, &events) {
+                            return;
+                        }
+                        match grid.generate_layers() {
+                            Ok(()) => if events.send(Event::Layers(grid.layers.clone())).is_err() {
+                                return;
+                            }
+                            Err(error) => {
+                                if !send_failure(&grid, &events, Failure {error, can_backtrack: false}) {
+                                    return;
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    grid.save_trace();
+                    if events.send(Event::Finished).is_err() {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+        if !send_changes(&mut grid, &events) {
+            return;
+        }
+        if watching && !send_next_pick(&mut grid, &events, &mut sent_pick) {
+            return;
+        }
+    }
+}
+
+/// Collapses a tile, remembering how to undo it. If it contradicts, the grid is left as it was and the choice is kept for backtracking
+fn collapse (grid: &mut TileGrid, history: &mut VecDeque<Decision>, failed: &mut Option<(i32, i32, TileChoice)>, x: i32, y: i32) -> Result<(), WfcError> {
+    let chosen = grid.tilegrid[x as usize][y as usize].choose(&mut grid.rng)?;
+    match grid.collapse_to(x, y, chosen) {
+        Ok(changes) => {
+            history.push_back(Decision {x, y, chosen, changes});
+            if history.len() > MAX_HISTORY {
+                history.pop_front();
+            }
+            return Ok(());
+        }
+        Err(error) => {
+            *failed = Some((x, y, chosen));
+            return Err(error);
+        }
+    }
+}
+
+/// Rules out the choice that contradicted. If that contradicts too, the collapse before it was wrong,
+/// so it's undone and ruled out instead, and so on back through the history
+fn backtrack (grid: &mut TileGrid, history: &mut VecDeque<Decision>, choice: (i32, i32, TileChoice)) -> Result<(), WfcError> {
+    let (mut x, mut y, mut chosen) = choice;
+    loop {
+        match grid.forbid(x, y, chosen) {
+            Ok(changes) => {
+                // Ruling the choice out depended on every collapse before it, so it's undone along with the latest one
+                if let Some(decision) = history.back_mut() {
+                    decision.changes.extend(changes);
+                }
+                return Ok(());
+            }
+            Err(error) => match history.pop_back() {
+                Some(decision) => {
+                    grid.roll_back(decision.changes);
+                    (x, y, chosen) = (decision.x, decision.y, decision.chosen);
+                }
+                None => return Err(error),
+            },
+        }
+    }
+}
+
+/// Picks the next tile to collapse, falling back to searching the whole grid when pick_index's sampling misses every undecided tile
+pub fn next_index (grid: &mut TileGrid) -> Option<(i32, i32)> {
+    if let Some(indices) = grid.pick_index() {
+        return Some(indices);
+    }
+    for i in 0..grid.width {
+        for j in 0..grid.height {
+            if grid.tilegrid[i as usize][j as usize].possible_tiles.len() > 1 {
+                return Some((i, j));
+            }
+        }
+    }
+    return None;
+}
+
+/// Works out which tile next_index will pick, without changing what it will pick
+fn peek_next_index (grid: &mut TileGrid) -> Option<(i32, i32)> {
+    let rng = grid.rng.clone();
+    let next = next_index(grid);
+    grid.rng = rng;
+    return next;
+}
+
+/// Sends the tile that will be collapsed next if it's different to the last one sent, returning false if the viewer has gone
+fn send_next_pick (grid: &mut TileGrid, events: &Sender<Event>, sent_pick: &mut Option<Option<(i32, i32)>>) -> bool {
+    let next_pick = peek_next_index(grid);
+    if *sent_pick == Some(next_pick) {
+        return true;
+    }
+    *sent_pick = Some(next_pick);
+    return events.send(Event::NextPick(next_pick)).is_ok();
+}
+
+/// Tells the viewer the solver stopped, saving the trace being recorded since this is where generation ends.
+/// Returns false if the viewer has gone
+fn send_failure (grid: &TileGrid, events: &Sender<Event>, failure: Failure) -> bool {
+    grid.save_trace();
+    return events.send(Event::Failed(failure)).is_ok();
+}
+
+/// Sends every tile that changed since the last call to the viewer, returning false if the viewer has gone
+fn send_changes (grid: &mut TileGrid, events: &Sender<Event>) -> bool {
+    for (x, y) in grid.take_dirty() {
+        let tile = grid.tilegrid[x as usize][y as usize].clone();
+        if events.send(Event::TileChanged(x, y, tile)).is_err() {
+            return false;
+        }
+    }
+    return true;
+}
+
+
+// The tests use the castle's tiles, in a 2x1 grid so only the tiles either side of each other matter
+#[cfg(all(test, feature = "castle"))]
+mod tests {
+    use super::*;
+
+    fn tile (texture: &str) -> TileChoice {
+        return *tileset::current().tiles.iter().find(|tileopt| tileopt.texture == texture).unwrap();
+    }
+
+    fn textures (grid: &TileGrid, x: i32, y: i32) -> Vec<&'static str> {
+        return grid.tilegrid[x as usize][y as usize].possible_tiles.iter().map(|tileopt| tileopt.texture).collect();
+    }
+
+    /// A grid with a wall top collapsed on the left, so the right has to carry the top of the wall on
+    fn wall_top () -> (TileGrid, VecDeque<Decision>) {
+        let mut grid = TileGrid::new(2, 1).unwrap();
+        let chosen = tile("Wall T");
+        let changes = grid.collapse_to(0, 0, chosen).unwrap();
+        let history = VecDeque::from([Decision {x: 0, y: 0, chosen, changes}]);
+        return (grid, history);
+    }
+
+    #[test]
+    fn backtracking_rules_out_the_choice_that_contradicted () {
+        let (mut grid, mut history) = wall_top();
+        assert_eq!(textures(&grid, 1, 0), vec!["Wall T", "Wall TR", "Wall LC"]);
+
+        backtrack(&mut grid, &mut history, (1, 0, tile("Wall T"))).unwrap();
+        assert_eq!(textures(&grid, 1, 0), vec!["Wall TR", "Wall LC"]);
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn backtracking_a_tile_with_no_options_left_undoes_the_collapse_before_it () {
+        let (mut grid, mut history) = wall_top();
+        backtrack(&mut grid, &mut history, (1, 0, tile("Wall T"))).unwrap();
+        backtrack(&mut grid, &mut history, (1, 0, tile("Wall TR"))).unwrap();
+        assert_eq!(textures(&grid, 1, 0), vec!["Wall LC"]);
+
+        // Ruling out the last option contradicts, so the wall top on the left is rolled back and ruled out instead
+        backtrack(&mut grid, &mut history, (1, 0, tile("Wall LC"))).unwrap();
+        assert!(history.is_empty());
+        assert_eq!(textures(&grid, 0, 0), vec!["Wall", "Wall L", "Wall R", "Wall TL", "Wall TR", "Wall LC", "Wall RC", "Sky"]);
+        assert_eq!(textures(&grid, 1, 0), vec!["Wall", "Wall L", "Wall R", "Wall T", "Wall TL", "Wall TR", "Wall LC", "Wall RC", "Sky"]);
+    }
+
+    #[test]
+    fn rolling_back_puts_the_tiles_back_as_they_were () {
+        let blank = TileGrid::new(2, 1).unwrap();
+        let (mut grid, mut history) = wall_top();
+        grid.roll_back(history.pop_back().unwrap().changes);
+        assert_eq!(textures(&grid, 0, 0), textures(&blank, 0, 0));
+        assert_eq!(textures(&grid, 1, 0), textures(&blank, 1, 0));
+    }
+}
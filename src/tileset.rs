@@ -0,0 +1,552 @@
+use crate::*;
+use crate::inspector::opposite;
+use crate::big_tiles::{BigTile, Part};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The tiles the wavefunction picks from and the rules for which connections fit together.
+/// The wavefunction's hardcoded tiles are only the default, the tileset can be edited and saved while running
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tileset {
+    pub tiles: Vec<TileChoice>,
+    /// Whether each connection can connect to each other connection, indexed by their position in Connection::ALL.
+    /// Should be symmetric, since the order connections are checked in must not matter, but one way rules can be loaded from files
+    connects: Vec<Vec<bool>>,
+    /// Textures which are drawn as one of several other textures, with the weight of each. Only changes how tiles look,
+    /// the solver never sees them, so a tile with several looks is still one option
+    pub variants: HashMap<&'static str, Vec<(&'static str, i32)>>,
+    /// Tiles covering more than one cell. Their parts are in tiles like any other tile
+    pub big_tiles: Vec<BigTile>,
+    /// Which big tile each part texture belongs to
+    parts: HashMap<&'static str, Part>,
+    /// Layers generated on top of the grid once it's decided, bottom first
+    pub layers: Vec<Layer>,
+}
+
+/// A grid of tiles stacked on top of the one below it, so things like windows can be drawn over whatever wall is behind them.
+/// Its tiles connect to each other the same way the grid's do, unless the layer is free, and can also be limited to what's under them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub name: &'static str,
+    pub tiles: Vec<TileChoice>,
+    /// Whether the layer's tiles can go next to anything. A free layer's tiles never restrict each other, so their connections
+    /// are never read and only what's under them limits where they go
+    pub free: bool,
+    /// Tiles which can only sit on a tile with this connection on every side, by texture. Tiles that aren't listed can sit on anything
+    pub on: HashMap<&'static str, Connection>,
+}
+
+/// The tileset every grid is using, shared between the viewer and the solver threads
+static CURRENT: RwLock<Option<Arc<Tileset>>> = RwLock::new(None);
+/// Texture names read from files, kept forever so tiles can refer to them with &'static str
+static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// The tileset in use, which is the default one until another is set
+pub fn current () -> Arc<Tileset> {
+    if let Some(tileset) = CURRENT.read().unwrap().as_ref() {
+        return tileset.clone();
+    }
+    let mut current = CURRENT.write().unwrap();
+    return current.get_or_insert_with(|| Arc::new(Tileset::default())).clone();
+}
+
+/// Switches every grid over to a new tileset. Tiles which already exist keep their old options until they're reset
+pub fn set_current (tileset: Tileset) {
+    *CURRENT.write().unwrap() = Some(Arc::new(tileset));
+}
+
+/// A &'static str with the same contents as name, reusing one from before if there is one
+pub fn intern (name: &str) -> &'static str {
+    let mut names = NAMES.lock().unwrap();
+    if let Some(interned) = names.iter().find(|interned| **interned == name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.push(interned);
+    return interned;
+}
+
+/// The connection's position in Connection::ALL
+fn connection_index (connection: Connection) -> usize {
+    return connection.index();
+}
+
+/// A connection's name, as written in tileset files
+pub fn connection_name (connection: Connection) -> String {
+    return format!("{:?}", connection);
+}
+
+fn parse_connection (name: &str) -> Result<Connection, String> {
+    return Connection::ALL.iter().copied().find(|connection| connection_name(*connection) == name)
+        .ok_or(format!("Unknown connection \"{}\"", name));
+}
+
+/// Mixes a seed and a position into a number that looks random but is always the same for them
+fn position_hash (seed: u64, x: i32, y: i32) -> u64 {
+    // splitmix64's finaliser
+    let mut hash = seed ^ ((x as u32 as u64) << 32 | y as u32 as u64);
+    hash = hash.wrapping_add(0x9e3779b97f4a7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return hash ^ (hash >> 31);
+}
+
+/// A tile's line in a tileset file
+fn tile_line (tileopt: &TileChoice) -> String {
+    let connections: Vec<String> = tileopt.connections.iter().map(|connection| connection_name(*connection)).collect();
+    return format!("tile {} {} {} {} {} {}", tileopt.weight, tileopt.flipx, tileopt.flipy, tileopt.rot90, connections.join(" "), tileopt.texture);
+}
+
+fn parse_bool (word: &str) -> Result<bool, String> {
+    return word.parse().map_err(|_| format!("Expected true or false, found \"{}\"", word));
+}
+
+impl Default for Tileset {
+    /// The wavefunction's hardcoded tiles and rules
+    fn default () -> Self {
+        let connects = Connection::ALL.iter()
+            .map(|&a| Connection::ALL.iter().map(|&b| Connection::default_can_connect(a, b)).collect())
+            .collect();
+        let mut tileset = Self {
+            tiles: UndecidedTile::default_tiles(),
+            connects,
+            variants: UndecidedTile::default_variants().into_iter().collect(),
+            big_tiles: Vec::new(),
+            parts: HashMap::new(),
+            layers: UndecidedTile::default_layers(),
+        };
+        for big in UndecidedTile::default_big_tiles() {
+            tileset.add_big_tile(big);
+        }
+        return tileset;
+    }
+}
+
+impl Tileset {
+    pub fn can_connect (&self, con1: Connection, con2: Connection) -> bool {
+        return self.connects[connection_index(con1)][connection_index(con2)];
+    }
+
+    /// Sets whether two connections can connect, both ways round
+    pub fn set_can_connect (&mut self, con1: Connection, con2: Connection, can_connect: bool) {
+        let (a, b) = (connection_index(con1), connection_index(con2));
+        self.connects[a][b] = can_connect;
+        self.connects[b][a] = can_connect;
+    }
+
+    /// Sets whether con1 can connect to con2 without changing whether con2 can connect to con1
+    pub fn set_can_connect_one_way (&mut self, con1: Connection, con2: Connection, can_connect: bool) {
+        self.connects[connection_index(con1)][connection_index(con2)] = can_connect;
+    }
+
+    /// Every pair of connections where the first can connect to the second but not the other way round
+    pub fn one_way_connections (&self) -> Vec<(Connection, Connection)> {
+        let mut one_way = Vec::new();
+        for &con1 in Connection::ALL.iter() {
+            for &con2 in Connection::ALL.iter() {
+                if self.can_connect(con1, con2) && !self.can_connect(con2, con1) {
+                    one_way.push((con1, con2));
+                }
+            }
+        }
+        return one_way;
+    }
+
+    /// Adds a big tile along with a tile for each of its parts
+    pub fn add_big_tile (&mut self, big: BigTile) {
+        let index = self.big_tiles.len();
+        // Parts are listed row by row
+        for (i, part) in big.parts().into_iter().enumerate() {
+            let (x, y) = (i as i32 % big.width, i as i32 / big.width);
+            self.parts.insert(part.texture, Part {big: index, x, y});
+            self.tiles.push(part);
+        }
+        self.big_tiles.push(big);
+    }
+
+    pub fn has_big_tiles (&self) -> bool {
+        return !self.big_tiles.is_empty();
+    }
+
+    /// Which part of a big tile a texture is, if it's one
+    pub fn part (&self, texture: &str) -> Option<Part> {
+        return self.parts.get(texture).copied();
+    }
+
+    /// The part next to a part in direction, if it's still inside the same big tile
+    fn neighbour_part (&self, part: Part, direction: usize) -> Option<Part> {
+        if !self.big_tiles[part.big].inside(part, direction) {
+            return None;
+        }
+        let (dx, dy) = [(0, -1), (1, 0), (0, 1), (-1, 0)][direction];
+        return Some(Part {x: part.x + dx, y: part.y + dy, ..part});
+    }
+
+    /// Whether a tile's side in direction faces another part of its big tile, so it can't be at the edge of the grid
+    pub fn faces_inside (&self, tileopt: &TileChoice, direction: usize) -> bool {
+        return self.part(tileopt.texture).is_some_and(|part| self.big_tiles[part.big].inside(part, direction));
+    }
+
+    /// Every cell a tile covers if it's placed at (x, y), with the tile that goes in each. That's every part of its big tile,
+    /// or only (x, y) itself for a tile that isn't part of one
+    pub fn big_tile_cells (&self, x: i32, y: i32, tileopt: TileChoice) -> Vec<(i32, i32, TileChoice)> {
+        let part = match self.part(tileopt.texture) {
+            Some(part) => part,
+            None => return vec![(x, y, tileopt)],
+        };
+        let big = &self.big_tiles[part.big];
+        let mut cells = Vec::new();
+        for py in 0..big.height {
+            for px in 0..big.width {
+                let texture = big.part_texture(px, py);
+                // The tileset's own copy of each part, since it might have been edited
+                if let Some(other) = self.tiles.iter().find(|other| other.texture == texture) {
+                    cells.push((x - part.x + px, y - part.y + py, *other));
+                }
+            }
+        }
+        return cells;
+    }
+
+    /// Whether a tile on layer can sit on top of the tile below it. The sides of big tiles' parts that face each other
+    /// don't say what the part is, so parts only have tiles that can sit on anything on top of them
+    pub fn sits_on (&self, layer: usize, tileopt: &TileChoice, below: &TileChoice) -> bool {
+        return match self.layers[layer].on.get(tileopt.texture) {
+            Some(connection) => self.part(below.texture).is_none() && below.connections.iter().all(|below_connection| below_connection == connection),
+            None => true,
+        };
+    }
+
+    /// The texture a tile is drawn with at (x, y). A texture with variants picks one by weight from a hash of the seed and position,
+    /// so a tile keeps the same look from frame to frame, and any other texture is drawn as itself
+    pub fn variant (&self, texture: &'static str, seed: u64, x: i32, y: i32) -> &'static str {
+        let variants = match self.variants.get(texture) {
+            Some(variants) if !variants.is_empty() => variants,
+            _ => return texture,
+        };
+        let total: u64 = variants.iter().map(|(_, weight)| max(*weight, 0) as u64).sum();
+        if total == 0 {
+            return variants[0].0;
+        }
+        let mut pick = position_hash(seed, x, y) % total;
+        for (variant, weight) in variants.iter() {
+            let weight = max(*weight, 0) as u64;
+            if pick < weight {
+                return variant;
+            }
+            pick -= weight;
+        }
+        return variants[0].0;
+    }
+
+    /// The texture to show for a tile that isn't anywhere on the grid, like in a list of options
+    pub fn preview (&self, texture: &'static str) -> &'static str {
+        return match self.variants.get(texture).and_then(|variants| variants.first()) {
+            Some((variant, _)) => variant,
+            None => texture,
+        };
+    }
+
+    /// The tileset as a file. Each tile is a line of
+    /// `tile <weight> <flipx> <flipy> <rot90> <up> <right> <down> <left> <texture>`,
+    /// each pair of different connections that can connect is a line of `connect <a> <b>`, or `oneway <a> <b>` if only a can connect to b,
+    /// each look a texture can be drawn with is a line of `variant <weight> <texture> = <variant texture>`,
+    /// and each big tile is a line of `big <width> <height> <weight> <up...> <right...> <down...> <left...> <texture>`,
+    /// with one connection per cell along each side. Parts of big tiles aren't written as tiles, they're made from the big tile.
+    /// Each layer starts with a line of `layer <name>`, and the tile lines after it are that layer's, along with a line of
+    /// `on <connection> <texture>` for each of its tiles that can only sit on tiles with that connection all round
+    pub fn to_text (&self) -> String {
+        let mut lines = vec!["# tile <weight> <flipx> <flipy> <rot90> <up> <right> <down> <left> <texture>".to_string()];
+        for tileopt in self.tiles.iter().filter(|tileopt| self.part(tileopt.texture).is_none()) {
+            lines.push(tile_line(tileopt));
+        }
+        lines.push("# connect <a> <b> or oneway <a> <b>, connections always connect to themselves unless there's a `separate <a>` line".to_string());
+        for (a, &con1) in Connection::ALL.iter().enumerate() {
+            if !self.connects[a][a] {
+                lines.push(format!("separate {}", connection_name(con1)));
+            }
+            for (b, &con2) in Connection::ALL.iter().enumerate().skip(a + 1) {
+                match (self.connects[a][b], self.connects[b][a]) {
+                    (true, true) => lines.push(format!("connect {} {}", connection_name(con1), connection_name(con2))),
+                    (true, false) => lines.push(format!("oneway {} {}", connection_name(con1), connection_name(con2))),
+                    (false, true) => lines.push(format!("oneway {} {}", connection_name(con2), connection_name(con1))),
+                    (false, false) => {}
+                }
+            }
+        }
+        lines.push("# variant <weight> <texture> = <variant texture>".to_string());
+        let mut textures: Vec<&&'static str> = self.variants.keys().collect();
+        textures.sort();
+        for texture in textures {
+            for (variant, weight) in self.variants[texture].iter() {
+                lines.push(format!("variant {} {} = {}", weight, texture, variant));
+            }
+        }
+        lines.push("# big <width> <height> <weight> <up...> <right...> <down...> <left...> <texture>".to_string());
+        for big in self.big_tiles.iter() {
+            let connections: Vec<String> = big.sides.iter().flatten().map(|connection| connection_name(*connection)).collect();
+            lines.push(format!("big {} {} {} {} {}", big.width, big.height, big.weight, connections.join(" "), big.texture));
+        }
+        for layer in self.layers.iter() {
+            lines.push(format!("layer {}", layer.name));
+            if layer.free {
+                lines.push("free".to_string());
+            }
+            lines.extend(layer.tiles.iter().map(tile_line));
+            let mut textures: Vec<&&'static str> = layer.on.keys().collect();
+            textures.sort();
+            for texture in textures {
+                lines.push(format!("on {} {}", connection_name(layer.on[texture]), texture));
+            }
+        }
+        return lines.join("\n") + "\n";
+    }
+
+    /// Reads a tileset written by to_text
+    pub fn from_text (text: &str) -> Result<Self, String> {
+        let count = Connection::ALL.len();
+        let mut connects = vec![vec![false; count]; count];
+        for (i, row) in connects.iter_mut().enumerate() {
+            row[i] = true;
+        }
+        let mut tileset = Self {tiles: Vec::new(), connects, variants: HashMap::new(), big_tiles: Vec::new(), parts: HashMap::new(), layers: Vec::new()};
+        // Tiles go in the last layer started, or the grid itself before any have been
+        let mut layer: Option<usize> = None;
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "tile" if words.len() >= 10 => {
+                    let weight = words[1].parse().map_err(|_| format!("Expected a weight, found \"{}\"", words[1]))?;
+                    let connections = [parse_connection(words[5])?, parse_connection(words[6])?, parse_connection(words[7])?, parse_connection(words[8])?];
+                    // Textures can have spaces in their names, so the texture is the rest of the line
+                    let texture = intern(&words[9..].join(" "));
+                    let tileopt = TileChoice {
+                        connections,
+                        weight,
+                        texture,
+                        flipx: parse_bool(words[2])?,
+                        flipy: parse_bool(words[3])?,
+                        rot90: parse_bool(words[4])?,
+                    };
+                    match layer {
+                        Some(layer) => tileset.layers[layer].tiles.push(tileopt),
+                        None => tileset.tiles.push(tileopt),
+                    }
+                }
+                "layer" if words.len() >= 2 => {
+                    tileset.layers.push(Layer {name: intern(&words[1..].join(" ")), tiles: Vec::new(), free: false, on: HashMap::new()});
+                    layer = Some(tileset.layers.len() - 1);
+                }
+                "free" if words.len() == 1 => {
+                    let layer = layer.ok_or(format!("\"{}\" has to come after a layer line", line))?;
+                    tileset.layers[layer].free = true;
+                }
+                "on" if words.len() >= 3 => {
+                    let layer = layer.ok_or(format!("\"{}\" has to come after a layer line", line))?;
+                    tileset.layers[layer].on.insert(intern(&words[2..].join(" ")), parse_connection(words[1])?);
+                }
+                "connect" if words.len() == 3 => tileset.set_can_connect(parse_connection(words[1])?, parse_connection(words[2])?, true),
+                "oneway" if words.len() == 3 => tileset.set_can_connect_one_way(parse_connection(words[1])?, parse_connection(words[2])?, true),
+                "variant" if words.len() >= 3 => {
+                    let weight = words[1].parse().map_err(|_| format!("Expected a weight, found \"{}\"", words[1]))?;
+                    // Both textures can have spaces in their names, so they're split by the =
+                    let rest = &line["variant".len()..].trim_start()[words[1].len()..];
+                    let (texture, variant) = rest.split_once('=').ok_or(format!("Couldn't read the line \"{}\"", line))?;
+                    tileset.variants.entry(intern(texture.trim())).or_default().push((intern(variant.trim()), weight));
+                }
+                "big" if words.len() >= 5 => {
+                    let number = |word: &str| word.parse::<i32>().map_err(|_| format!("Expected a number, found \"{}\"", word));
+                    let (width, height, weight) = (number(words[1])?, number(words[2])?, number(words[3])?);
+                    let lengths = [width, height, width, height].map(|length| max(length, 0) as usize);
+                    let count: usize = lengths.iter().sum();
+                    if words.len() < 5 + count {
+                        return Err(format!("A {}x{} big tile needs {} connections, in \"{}\"", width, height, count, line));
+                    }
+                    let mut connections = words[4..4 + count].iter().map(|word| parse_connection(word));
+                    let mut sides: [Vec<Connection>; 4] = Default::default();
+                    for (side, length) in sides.iter_mut().zip(lengths) {
+                        for _ in 0..length {
+                            side.push(connections.next().unwrap()?);
+                        }
+                    }
+                    let big = BigTile {texture: intern(&words[4 + count..].join(" ")), width, height, weight, sides};
+                    if !big.is_valid() {
+                        return Err(format!("Couldn't read the big tile \"{}\"", line));
+                    }
+                    tileset.add_big_tile(big);
+                }
+                "separate" if words.len() == 2 => tileset.set_can_connect(parse_connection(words[1])?, parse_connection(words[1])?, false),
+                _ => return Err(format!("Couldn't read the line \"{}\"", line)),
+            }
+        }
+        if tileset.tiles.is_empty() {
+            return Err("The tileset has no tiles".to_string());
+        }
+        if let Some(empty) = tileset.layers.iter().find(|layer| layer.tiles.is_empty()) {
+            return Err(format!("The layer \"{}\" has no tiles", empty.name));
+        }
+        return Ok(tileset);
+    }
+
+    /// Whether tile b can sit next to tile a in direction, looking at it from a's side.
+    /// Where a side faces inside a big tile, only the part that's next to it in the big tile can be there
+    pub fn allows (&self, a: &TileChoice, b: &TileChoice, direction: usize) -> bool {
+        if self.has_big_tiles() {
+            let (part_a, part_b) = (self.part(a.texture), self.part(b.texture));
+            if let Some(part) = part_a.filter(|part| self.big_tiles[part.big].inside(*part, direction)) {
+                return self.neighbour_part(part, direction) == part_b;
+            }
+            if part_b.is_some_and(|part| self.big_tiles[part.big].inside(part, opposite(direction))) {
+                return false;
+            }
+        }
+        return self.can_connect(a.connections[direction], b.connections[opposite(direction)]);
+    }
+
+    /// For one direction, whether each tile allows each other tile next to it, indexed [tile][neighbour]
+    pub fn adjacency (&self, direction: usize) -> Vec<Vec<bool>> {
+        return self.tiles.iter()
+            .map(|a| self.tiles.iter().map(|b| self.allows(a, b, direction)).collect())
+            .collect();
+    }
+
+    /// Every tile and direction where no tile can be its neighbour. Dead tiles can never be placed away from the edge of the grid
+    pub fn dead_tiles (&self) -> Vec<(usize, usize)> {
+        let mut dead = Vec::new();
+        for (i, a) in self.tiles.iter().enumerate() {
+            for direction in 0..4 {
+                if !self.tiles.iter().any(|b| self.allows(a, b, direction)) {
+                    dead.push((i, direction));
+                }
+            }
+        }
+        return dead;
+    }
+
+    /// Every pair of tiles where a allows b in direction but b doesn't allow a from the other side, as (a, b, direction).
+    /// Each pair is only listed once, from the side that allows it
+    pub fn asymmetric_rules (&self) -> Vec<(usize, usize, usize)> {
+        let mut asymmetric = Vec::new();
+        for (i, a) in self.tiles.iter().enumerate() {
+            for (j, b) in self.tiles.iter().enumerate() {
+                for direction in 0..4 {
+                    if self.allows(a, b, direction) && !self.allows(b, a, opposite(direction)) {
+                        asymmetric.push((i, j, direction));
+                    }
+                }
+            }
+        }
+        return asymmetric;
+    }
+
+    pub fn save (&self, path: &str) -> std::io::Result<()> {
+        return fs::write(path, self.to_text());
+    }
+
+    pub fn load (path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Couldn't read {}: {}", path, error))?;
+        return Self::from_text(&text);
+    }
+}
+
+/// A tile followed by each of its turns that has different connections
+pub fn turns (tileopt: &TileChoice) -> Vec<TileChoice> {
+    let mut turns = Vec::<TileChoice>::new();
+    let mut turned = *tileopt;
+    for _ in 0..4 {
+        if !turns.iter().any(|tile| tile.connections == turned.connections) {
+            turns.push(turned);
+        }
+        turned = rotate_clockwise(&turned);
+    }
+    return turns;
+}
+
+/// The same tile turned 90 degrees clockwise, texture and connections both
+pub fn rotate_clockwise (tileopt: &TileChoice) -> TileChoice {
+    let [up, right, down, left] = tileopt.connections;
+    // Textures are flipped before they're rotated, so turning a rotated tile again is the same as flipping it both ways
+    let (flipx, flipy, rot90) = if tileopt.rot90 {(!tileopt.flipx, !tileopt.flipy, false)} else {(tileopt.flipx, tileopt.flipy, true)};
+    return TileChoice {
+        connections: [left, up, right, down],
+        flipx,
+        flipy,
+        rot90,
+        ..*tileopt
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tileset of one tile with two looks and a free layer on top, using whichever connection the wavefunction lists first
+    fn small_tileset () -> Tileset {
+        let a = connection_name(Connection::ALL[0]);
+        let text = [
+            format!("tile 3 false false false {a} {a} {a} {a} Grass"),
+            "variant 1 Grass = Grass 1".to_string(),
+            "variant 0 Grass = Grass 2".to_string(),
+            "layer Flowers".to_string(),
+            "free".to_string(),
+            format!("tile 1 false false false {a} {a} {a} {a} Flower"),
+            format!("on {a} Flower"),
+        ].join("\n");
+        return Tileset::from_text(&text).unwrap();
+    }
+
+    #[test]
+    fn a_tileset_file_is_read_back_as_the_same_tileset () {
+        let tileset = small_tileset();
+        assert_eq!(tileset.tiles.len(), 1);
+        assert_eq!((tileset.tiles[0].texture, tileset.tiles[0].weight), ("Grass", 3));
+        assert_eq!(tileset.variants["Grass"], vec![("Grass 1", 1), ("Grass 2", 0)]);
+        assert_eq!((tileset.layers[0].name, tileset.layers[0].free), ("Flowers", true));
+        assert_eq!(tileset.layers[0].on["Flower"], Connection::ALL[0]);
+        assert_eq!(Tileset::from_text(&tileset.to_text()), Ok(tileset));
+
+        let tileset = Tileset::default();
+        assert_eq!(Tileset::from_text(&tileset.to_text()), Ok(tileset));
+    }
+
+    #[test]
+    fn layer_tiles_only_sit_on_tiles_with_their_connection_on_every_side () {
+        let tileset = small_tileset();
+        let flower = tileset.layers[0].tiles[0];
+        let grass = tileset.tiles[0];
+        assert!(tileset.sits_on(0, &flower, &grass));
+
+        let mut edge = grass;
+        edge.connections[2] = Connection::ALL[1];
+        assert!(!tileset.sits_on(0, &flower, &edge));
+        // A tile without an on line can sit on anything
+        let weed = TileChoice {texture: "Weed", ..flower};
+        assert!(tileset.sits_on(0, &weed, &edge));
+    }
+
+    #[test]
+    fn position_hashes_are_splitmix64 () {
+        // splitmix64's first number from a state of 0
+        assert_eq!(position_hash(0, 0, 0), 0xe220a8397b1dcdaf);
+        assert_eq!(position_hash(7, 3, -2), position_hash(7, 3, -2));
+        assert_ne!(position_hash(7, 3, -2), position_hash(7, -2, 3));
+        assert_ne!(position_hash(7, 3, -2), position_hash(8, 3, -2));
+    }
+
+    #[test]
+    fn variants_are_the_same_for_the_same_seed_and_position () {
+        let mut tileset = small_tileset();
+        // Grass 2 has no weight, so it's never picked
+        for x in 0..10 {
+            assert_eq!(tileset.variant("Grass", 1, x, 0), "Grass 1");
+        }
+        assert_eq!(tileset.variant("Flower", 1, 0, 0), "Flower");
+
+        tileset.variants.insert("Grass", vec![("Grass 1", 1), ("Grass 2", 1)]);
+        let looks = |seed| (0..100).map(|i| tileset.variant("Grass", seed, i % 10, i / 10)).collect::<Vec<&str>>();
+        assert_eq!(looks(1), looks(1));
+        assert_ne!(looks(1), looks(2));
+        assert!(looks(1).contains(&"Grass 1") && looks(1).contains(&"Grass 2"));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::*;
+use crate::inspector::{option_name, DIRECTION_NAMES};
+use std::fs;
+
+/// Where traces are saved to and loaded from
+pub const TRACE_PATH: &str = "trace.txt";
+
+/// One change to the grid. Options are written by name, and only the forward change is kept since replays work out how to undo them
+#[derive(Clone, Debug)]
+pub enum TraceEvent {
+    /// The grid was replaced with a blank one of this size, followed by a Set for every tile that isn't blank
+    Grid {width: i32, height: i32},
+    /// A tile's options were replaced, by an edit rather than by collapsing
+    Set {x: i32, y: i32, options: Vec<TileChoice>},
+    /// A tile was collapsed to one option
+    Collapse {x: i32, y: i32, chosen: TileChoice},
+    /// Options were removed from a tile because the neighbour in direction couldn't connect to them
+    Reduce {x: i32, y: i32, direction: usize, removed: Vec<TileChoice>},
+    /// A tile ran out of options
+    Contradiction {x: i32, y: i32},
+}
+
+/// Every change made to the grid since recording started, in order
+#[derive(Clone, Debug)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl TraceEvent {
+    /// The event as a line of the trace file
+    pub fn to_line (&self) -> String {
+        let names = |options: &[TileChoice]| options.iter().map(option_name).collect::<Vec<String>>().join("; ");
+        return match self {
+            TraceEvent::Grid {width, height} => format!("grid {} {}", width, height),
+            TraceEvent::Set {x, y, options} => format!("set {} {} | {}", x, y, names(options)),
+            TraceEvent::Collapse {x, y, chosen} => format!("collapse {} {} | {}", x, y, option_name(chosen)),
+            TraceEvent::Reduce {x, y, direction, removed} => format!("reduce {} {} {} | {}", x, y, DIRECTION_NAMES[*direction], names(removed)),
+            TraceEvent::Contradiction {x, y} => format!("contradiction {} {}", x, y),
+        };
+    }
+
+    /// Reads a line written by to_line
+    pub fn from_line (line: &str) -> Result<Self, String> {
+        let (head, options) = match line.split_once(" | ") {
+            Some((head, options)) => (head, Some(options)),
+            None => (line, None),
+        };
+        let words: Vec<&str> = head.split_whitespace().collect();
+        let number = |index: usize| -> Result<i32, String> {
+            return words.get(index).and_then(|word| word.parse().ok()).ok_or(format!("Expected a number in \"{}\"", line));
+        };
+        let options = || -> Result<Vec<TileChoice>, String> {
+            let blank = UndecidedTile::new();
+            let mut parsed = Vec::new();
+            for name in options.unwrap_or("").split("; ").filter(|name| !name.is_empty()) {
+                match blank.possible_tiles.iter().find(|tileopt| option_name(tileopt) == name) {
+                    Some(tileopt) => parsed.push(*tileopt),
+                    None => return Err(format!("Unknown option \"{}\"", name)),
+                }
+            }
+            return Ok(parsed);
+        };
+        return match words.first() {
+            Some(&"grid") => Ok(TraceEvent::Grid {width: number(1)?, height: number(2)?}),
+            Some(&"set") => Ok(TraceEvent::Set {x: number(1)?, y: number(2)?, options: options()?}),
+            Some(&"collapse") => match options()?.first() {
+                Some(chosen) => Ok(TraceEvent::Collapse {x: number(1)?, y: number(2)?, chosen: *chosen}),
+                None => Err(format!("No option collapsed to in \"{}\"", line)),
+            },
+            Some(&"reduce") => match DIRECTION_NAMES.iter().position(|name| Some(name) == words.get(3)) {
+                Some(direction) => Ok(TraceEvent::Reduce {x: number(1)?, y: number(2)?, direction, removed: options()?}),
+                None => Err(format!("Unknown direction in \"{}\"", line)),
+            },
+            Some(&"contradiction") => Ok(TraceEvent::Contradiction {x: number(1)?, y: number(2)?}),
+            _ => Err(format!("Unknown event \"{}\"", line)),
+        };
+    }
+}
+
+impl Trace {
+    /// Starts a trace from the grid as it is now
+    pub fn start (grid: &TileGrid) -> Self {
+        let mut trace = Self {events: Vec::new()};
+        trace.snapshot(grid);
+        return trace;
+    }
+
+    /// Records the whole grid, for when it's changed in a way that isn't a list of tile changes, like being shifted
+    pub fn snapshot (&mut self, grid: &TileGrid) {
+        self.events.push(TraceEvent::Grid {width: grid.width, height: grid.height});
+        let blank = UndecidedTile::new();
+        for i in 0..grid.width {
+            for j in 0..grid.height {
+                let tile = &grid.tilegrid[i as usize][j as usize];
+                if *tile != blank {
+                    self.events.push(TraceEvent::Set {x: i, y: j, options: tile.possible_tiles.clone()});
+                }
+            }
+        }
+    }
+
+    pub fn save (&self, path: &str) -> std::io::Result<()> {
+        let lines: Vec<String> = self.events.iter().map(TraceEvent::to_line).collect();
+        return fs::write(path, lines.join("\n") + "\n");
+    }
+
+    pub fn load (path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Couldn't read {}: {}", path, error))?;
+        let mut events = Vec::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            events.push(TraceEvent::from_line(line)?);
+        }
+        if !matches!(events.first(), Some(TraceEvent::Grid {..})) {
+            return Err(format!("{} doesn't start with a grid", path));
+        }
+        return Ok(Self {events});
+    }
+}
+
+/// How to undo a replayed event
+enum Undo {
+    Tile(i32, i32, UndecidedTile),
+    Grid(Box<TileGrid>),
+    Nothing,
+}
+
+/// Steps a grid backwards and forwards through a trace. The grid that was there before is kept to be put back afterwards
+pub struct Replay {
+    trace: Trace,
+    /// The number of events applied so far
+    pub position: usize,
+    /// The first event after the starting snapshot, stepping back stops here
+    start: usize,
+    undo: Vec<Undo>,
+    saved: TileGrid,
+}
+
+impl Replay {
+    /// Swaps the grid out for the state at the start of the trace, leaving the grid alone if the start doesn't fit in it
+    pub fn start (trace: Trace, grid: &mut TileGrid) -> Result<Self, WfcError> {
+        let saved = grid.clone();
+        let start = 1 + trace.events.iter().skip(1).take_while(|event| matches!(event, TraceEvent::Set {..})).count();
+        let mut replay = Self {trace, position: 0, start, undo: Vec::new(), saved};
+        while replay.position < start {
+            if let Err(error) = replay.step_forward(grid) {
+                *grid = replay.saved;
+                grid.mark_all_dirty();
+                return Err(error);
+            }
+        }
+        replay.undo.clear();
+        return Ok(replay);
+    }
+
+    /// Puts back the grid from before the replay started
+    pub fn finish (self, grid: &mut TileGrid) {
+        *grid = self.saved;
+        grid.mark_all_dirty();
+    }
+
+    pub fn len (&self) -> usize {
+        return self.trace.events.len();
+    }
+
+    /// The last event applied
+    pub fn current (&self) -> Option<&TraceEvent> {
+        return if self.position == 0 {None} else {self.trace.events.get(self.position - 1)};
+    }
+
+    /// Applies the next event. The trace could have been edited or recorded on a different grid, so an event that doesn't fit
+    /// in the grid is an error and isn't applied
+    pub fn step_forward (&mut self, grid: &mut TileGrid) -> Result<(), WfcError> {
+        let Some(event) = self.trace.events.get(self.position) else {return Ok(())};
+        match event {
+            TraceEvent::Grid {width, height} if *width < 1 || *height < 1 => {
+                return Err(WfcError::InvalidDimensions {width: *width, height: *height});
+            }
+            TraceEvent::Set {x, y, ..} | TraceEvent::Collapse {x, y, ..} | TraceEvent::Reduce {x, y, ..} if !grid.contains(*x, *y) => {
+                return Err(WfcError::OutOfBounds {x: *x, y: *y, width: grid.width, height: grid.height});
+            }
+            _ => {}
+        }
+        let tileset = tileset::current();
+        let undo = match event {
+            TraceEvent::Grid {width, height} => {
+                let old = std::mem::replace(grid, TileGrid::blank(*width, *height));
+                Undo::Grid(Box::new(old))
+            }
+            TraceEvent::Set {x, y, options} => {
+                let old = grid.tilegrid[*x as usize][*y as usize].clone();
+                grid.set_tile(&tileset, *x, *y, UndecidedTile {possible_tiles: options.clone()});
+                Undo::Tile(*x, *y, old)
+            }
+            TraceEvent::Collapse {x, y, chosen} => {
+                let old = grid.tilegrid[*x as usize][*y as usize].clone();
+                grid.set_tile(&tileset, *x, *y, UndecidedTile {possible_tiles: vec![*chosen]});
+                Undo::Tile(*x, *y, old)
+            }
+            TraceEvent::Reduce {x, y, removed, ..} => {
+                let old = grid.tilegrid[*x as usize][*y as usize].clone();
+                let mut tile = old.clone();
+                tile.possible_tiles.retain(|tileopt| !removed.contains(tileopt));
+                grid.set_tile(&tileset, *x, *y, tile);
+                Undo::Tile(*x, *y, old)
+            }
+            TraceEvent::Contradiction {..} => Undo::Nothing,
+        };
+        self.undo.push(undo);
+        self.position += 1;
+        return Ok(());
+    }
+
+    pub fn step_back (&mut self, grid: &mut TileGrid) {
+        if self.position <= self.start {
+            return;
+        }
+        match self.undo.pop() {
+            Some(Undo::Tile(x, y, tile)) => grid.set_tile(&tileset::current(), x, y, tile),
+            Some(Undo::Grid(old)) => {
+                *grid = *old;
+                grid.mark_all_dirty();
+            }
+            Some(Undo::Nothing) | None => {}
+        }
+        self.position -= 1;
+    }
+}
+
+// The tests record the castle's tiles
+#[cfg(all(test, feature = "castle"))]
+mod tests {
+    use super::*;
+
+    fn tile (texture: &str) -> TileChoice {
+        return *tileset::current().tiles.iter().find(|tileopt| tileopt.texture == texture).unwrap();
+    }
+
+    #[test]
+    fn a_saved_trace_replays_to_the_grid_it_was_recorded_on () {
+        let mut grid = TileGrid::new(4, 3).unwrap();
+        grid.start_trace();
+        grid.collapse_to(0, 0, tile("Sky")).unwrap();
+        grid.collapse_to(1, 2, tile("Wall")).unwrap();
+        let path = std::env::temp_dir().join(format!("wfc_trace_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        grid.trace.as_ref().unwrap().save(path).unwrap();
+        let trace = Trace::load(path);
+        let _ = fs::remove_file(path);
+        let trace = trace.unwrap();
+        assert_eq!(trace.events.len(), grid.trace.as_ref().unwrap().events.len());
+
+        // The replay starts from the snapshot taken when recording started, whatever size the grid being replayed on is
+        let mut viewer = TileGrid::new(2, 2).unwrap();
+        let before = viewer.tilegrid.clone();
+        let mut replay = Replay::start(trace, &mut viewer).unwrap();
+        assert_eq!((viewer.width, viewer.height), (4, 3));
+        let started = viewer.tilegrid.clone();
+        while replay.position < replay.len() {
+            replay.step_forward(&mut viewer).unwrap();
+        }
+        assert_eq!(viewer.tilegrid, grid.tilegrid);
+
+        for _ in 0..replay.len() {
+            replay.step_back(&mut viewer);
+        }
+        assert_eq!(viewer.tilegrid, started);
+        replay.finish(&mut viewer);
+        assert_eq!(viewer.tilegrid, before);
+    }
+
+    #[test]
+    fn events_outside_the_grid_are_errors () {
+        let mut grid = TileGrid::new(3, 3).unwrap();
+        let trace = Trace {events: vec![TraceEvent::Grid {width: 0, height: 2}]};
+        assert_eq!(Replay::start(trace, &mut grid).err(), Some(WfcError::InvalidDimensions {width: 0, height: 2}));
+        assert_eq!((grid.width, grid.height), (3, 3));
+
+        let trace = Trace {events: vec![TraceEvent::Grid {width: 2, height: 2}, TraceEvent::Collapse {x: 2, y: 0, chosen: tile("Sky")}]};
+        let mut replay = Replay::start(trace, &mut grid).unwrap();
+        assert_eq!(replay.step_forward(&mut grid), Err(WfcError::OutOfBounds {x: 2, y: 0, width: 2, height: 2}));
+        assert_eq!(replay.position, 1);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::*;
+use crate::inspector::{option_name, DIRECTION_NAMES};
+use crate::tileset::connection_name;
+use crate::renderers::manifest::{TextureManifest, TextureSource};
+use std::path::Path;
+
+/// The grid sizes the contradiction rate is measured at
+const TRIAL_SIZES: [i32; 3] = [5, 10, 20];
+const DEFAULT_TRIALS: usize = 20;
+
+/// How bad a problem with the tileset is. Errors make validate fail, warnings are only reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+struct Report {
+    problems: Vec<(Severity, String)>,
+}
+
+impl Report {
+    fn warn (&mut self, message: String) {
+        self.problems.push((Severity::Warning, message));
+    }
+
+    fn error (&mut self, message: String) {
+        self.problems.push((Severity::Error, message));
+    }
+
+    fn count (&self, severity: Severity) -> usize {
+        return self.problems.iter().filter(|(problem, _)| *problem == severity).count();
+    }
+}
+
+/// Runs `validate [tileset file] [--allow-asymmetric] [--trials <count>]`, printing what's wrong with the tileset.
+/// Returns the exit code, which is 1 if there were any errors
+pub fn run (args: &[String]) -> i32 {
+    let mut path = TILESET_PATH.to_string();
+    let mut allow_asymmetric = false;
+    let mut trials = DEFAULT_TRIALS;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--allow-asymmetric" => allow_asymmetric = true,
+            "--trials" => match args.next().and_then(|count| count.parse().ok()) {
+                Some(count) => trials = count,
+                None => {
+                    println!("--trials needs a number after it");
+                    return 2;
+                }
+            },
+            _ => path = arg.clone(),
+        }
+    }
+
+    let tileset = if Path::new(&path).exists() {
+        match Tileset::load(&path) {
+            Ok(tileset) => tileset,
+            Err(error) => {
+                println!("error: {}", error);
+                return 1;
+            }
+        }
+    } else if path == TILESET_PATH {
+        println!("{} doesn't exist, validating the default tileset", path);
+        Tileset::default()
+    } else {
+        println!("error: {} doesn't exist", path);
+        return 1;
+    };
+    println!("Validating {} tiles", tileset.tiles.len());
+
+    let mut report = Report {problems: Vec::new()};
+    check_unreachable(&tileset, &mut report);
+    check_asymmetric(&tileset, allow_asymmetric, &mut report);
+    check_textures(&tileset, &mut report);
+    check_weights(&tileset, &mut report);
+    check_layers(&tileset, &mut report);
+    for (severity, message) in report.problems.iter() {
+        let label = if *severity == Severity::Error {"error"} else {"warning"};
+        println!("{}: {}", label, message);
+    }
+
+    // The rate is only worth measuring when the weights won't make every run fail
+    if report.count(Severity::Error) == 0 && trials > 0 {
+        tileset::set_current(tileset);
+        println!("Contradiction rate over {} runs:", trials);
+        for size in TRIAL_SIZES {
+            let failed = contradictions(size, trials);
+            println!("  {}x{}: {}/{} ({:.0}%)", size, size, failed, trials, failed as f32 / trials as f32 * 100.0);
+        }
+    }
+
+    println!("{} errors, {} warnings", report.count(Severity::Error), report.count(Severity::Warning));
+    return if report.count(Severity::Error) > 0 {1} else {0};
+}
+
+/// Tiles that can never be placed away from the edge of the grid, because a side has no tile that fits it.
+/// The only sides the edges of the grid restrict are the sides of big tile parts that face the rest of their big tile (see faces_inside),
+/// and those always fit the next part, so they never show up here. Edge only tiles are only warnings, unless every tile ends up edge only.
+/// Tiles whose only partners are edge only are pushed to the edge too, so this keeps going until nothing else is removed
+fn check_unreachable (tileset: &Tileset, report: &mut Report) {
+    let mut reachable = tileset.clone();
+    let mut round = 0;
+    loop {
+        let dead = reachable.dead_tiles();
+        if dead.is_empty() {
+            break;
+        }
+        let mut removed = Vec::new();
+        for (i, direction) in dead {
+            let name = option_name(&reachable.tiles[i]);
+            if removed.contains(&i) {
+                continue;
+            }
+            if round == 0 {
+                report.warn(format!("{} can only be placed on the {} edge of the grid, nothing can go {} of it", name, DIRECTION_NAMES[direction], DIRECTION_NAMES[direction]));
+            }
+            else {
+                report.warn(format!("{} can only be placed near the edge of the grid, everything that can go {} of it is edge only", name, DIRECTION_NAMES[direction]));
+            }
+            removed.push(i);
+        }
+        // Remove from the back so the earlier indices stay the same
+        removed.sort();
+        for i in removed.into_iter().rev() {
+            reachable.tiles.remove(i);
+        }
+        if reachable.tiles.is_empty() {
+            report.error("Every tile is unreachable away from the edge of the grid, so only tiny grids can be collapsed".to_string());
+            break;
+        }
+        round += 1;
+    }
+}
+
+/// Connections that can connect one way but not the other. The solver checks whether the neighbour can connect to the tile
+/// being restricted, so a one way rule is applied differently depending on which of two tiles changed last
+fn check_asymmetric (tileset: &Tileset, allow_asymmetric: bool, report: &mut Report) {
+    if allow_asymmetric {
+        return;
+    }
+    for (con1, con2) in tileset.one_way_connections() {
+        report.warn(format!("{} can connect to {} but not the other way round (pass --allow-asymmetric if that's on purpose)", connection_name(con1), connection_name(con2)));
+    }
+}
+
+/// Tiles whose texture, or any of its variants, isn't in the texture manifest, or whose image or sheet tile doesn't exist
+fn check_textures (tileset: &Tileset, report: &mut Report) {
+    let manifest = match TextureManifest::load(TEXTURE_FOLDER) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            report.error(error.to_string());
+            return;
+        }
+    };
+    let mut checked = Vec::new();
+    for tileopt in tileset.tiles.iter().chain(tileset.layers.iter().flat_map(|layer| layer.tiles.iter())) {
+        // Parts of big tiles are drawn from their big tile's texture
+        let texture = match tileset.part(tileopt.texture) {
+            Some(part) => tileset.big_tiles[part.big].texture,
+            None => tileopt.texture,
+        };
+        // A texture with variants is never drawn itself, only its variants are
+        let textures = match tileset.variants.get(texture) {
+            Some(variants) => variants.iter().map(|(variant, _)| *variant).collect(),
+            None => vec![texture],
+        };
+        for texture in textures {
+            if checked.contains(&texture) {
+                continue;
+            }
+            checked.push(texture);
+            match manifest.source(texture) {
+                None => report.error(format!("The texture \"{}\" isn't in {}", texture, manifest.path().display())),
+                Some(TextureSource::Image(path)) if !path.exists() => report.error(format!("The texture \"{}\" is missing its image {}", texture, path.display())),
+                Some(TextureSource::Image(_)) => {}
+                Some(TextureSource::SheetTile {sheet, index}) => {
+                    let sheet = &manifest.sheets[sheet];
+                    match sheet.tile_count() {
+                        Err(error) => report.error(format!("The texture \"{}\" is on a sheet that can't be loaded. {}", texture, error)),
+                        Ok(count) if index >= count => report.error(format!("The texture \"{}\" is tile {} of {}, which only has {} tiles", texture, index, sheet.name, count)),
+                        Ok(_) => {}
+                    }
+                }
+            }
+        }
+    }
+    for (texture, variants) in tileset.variants.iter() {
+        if variants.iter().all(|(_, weight)| *weight <= 0) {
+            report.warn(format!("None of the variants of \"{}\" have a positive weight, so it's always drawn as {}", texture, tileset.preview(texture)));
+        }
+    }
+}
+
+/// Negative weights make picking an option fail, and so does a tile left with only zero weight options
+fn check_weights (tileset: &Tileset, report: &mut Report) {
+    for tileopt in tileset.tiles.iter().chain(tileset.layers.iter().flat_map(|layer| layer.tiles.iter())) {
+        if tileopt.weight < 0 {
+            report.error(format!("{} has a negative weight of {}", option_name(tileopt), tileopt.weight));
+        }
+        else if tileopt.weight == 0 {
+            report.warn(format!("{} has a weight of 0, so it's never picked and a tile left with only zero weight options can't be collapsed", option_name(tileopt)));
+        }
+    }
+}
+
+/// Layer tiles which can't sit on anything in the layer below them, since nothing there has their connection all round
+fn check_layers (tileset: &Tileset, report: &mut Report) {
+    for (layer, layer_tiles) in tileset.layers.iter().enumerate() {
+        let below = if layer == 0 {&tileset.tiles} else {&tileset.layers[layer - 1].tiles};
+        for tileopt in layer_tiles.tiles.iter() {
+            if !below.iter().any(|below_option| tileset.sits_on(layer, tileopt, below_option)) {
+                report.warn(format!("{} on the {} layer can never be placed, nothing under it has {} all round", option_name(tileopt), layer_tiles.name, connection_name(layer_tiles.on[tileopt.texture])));
+            }
+        }
+    }
+}
+
+/// Collapses blank grids of the given size and their layers until they finish or contradict, returning how many contradicted
+fn contradictions (size: i32, trials: usize) -> usize {
+    let mut failed = 0;
+    for _ in 0..trials {
+        let finished = TileGrid::new(size, size).and_then(|mut grid| {
+            while let Some((x, y)) = solver::next_index(&mut grid) {
+                grid.collapse_and_propegate(x, y)?;
+            }
+            return grid.generate_layers();
+        });
+        if finished.is_err() {
+            failed += 1;
+        }
+    }
+    return failed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validates a tileset saved to a file of its own, without measuring the contradiction rate
+    fn validate (tileset: &Tileset, name: &str) -> i32 {
+        let path = std::env::temp_dir().join(format!("wfc_validate_{}_{}.tileset", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        tileset.save(&path).unwrap();
+        let code = run(&[path.clone(), "--trials".to_string(), "0".to_string()]);
+        let _ = std::fs::remove_file(&path);
+        return code;
+    }
+
+    fn empty_report () -> Report {
+        return Report {problems: Vec::new()};
+    }
+
+    #[test]
+    fn the_exit_code_is_1_only_when_there_are_errors () {
+        let mut tileset = Tileset::default();
+        assert_eq!(validate(&tileset, "default"), 0);
+        tileset.tiles[0].weight = -1;
+        assert_eq!(validate(&tileset, "negative"), 1);
+
+        assert_eq!(run(&["no such tileset".to_string()]), 1);
+        assert_eq!(run(&["--trials".to_string(), "lots".to_string()]), 2);
+    }
+
+    #[test]
+    fn negative_weights_are_errors_and_zero_weights_are_warnings () {
+        let mut tileset = Tileset::default();
+        tileset.tiles[0].weight = -1;
+        tileset.tiles[1].weight = 0;
+        let mut report = empty_report();
+        check_weights(&tileset, &mut report);
+        assert_eq!((report.count(Severity::Error), report.count(Severity::Warning)), (1, 1));
+    }
+
+    #[test]
+    fn one_way_connections_are_warnings_unless_they_are_allowed () {
+        let (a, b) = (connection_name(Connection::ALL[0]), connection_name(Connection::ALL[1]));
+        let text = [
+            format!("tile 1 false false false {a} {a} {a} {a} A"),
+            format!("tile 1 false false false {b} {b} {b} {b} B"),
+            format!("oneway {a} {b}"),
+        ].join("\n");
+        let tileset = Tileset::from_text(&text).unwrap();
+        let mut report = empty_report();
+        check_asymmetric(&tileset, false, &mut report);
+        assert_eq!(report.count(Severity::Warning), 1);
+        let mut report = empty_report();
+        check_asymmetric(&tileset, true, &mut report);
+        assert!(report.problems.is_empty());
+    }
+
+    #[test]
+    fn layer_tiles_that_cant_sit_on_anything_are_warnings () {
+        let (a, b) = (connection_name(Connection::ALL[0]), connection_name(Connection::ALL[1]));
+        let text = [
+            format!("tile 1 false false false {a} {a} {a} {a} A"),
+            "layer Top".to_string(),
+            format!("tile 1 false false false {a} {a} {a} {a} On A"),
+            format!("on {a} On A"),
+            format!("tile 1 false false false {a} {a} {a} {a} On B"),
+            format!("on {b} On B"),
+        ].join("\n");
+        let tileset = Tileset::from_text(&text).unwrap();
+        let mut report = empty_report();
+        check_layers(&tileset, &mut report);
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].1.starts_with("On B"), "{}", report.problems[0].1);
+    }
+}
@@ -2,7 +2,15 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
-// pub mod islands;
-// pub mod colored_wires;
-// pub mod puzzle;
-pub mod castle;
\ No newline at end of file
+#[cfg(feature = "islands")]
+pub mod islands;
+#[cfg(feature = "colored_wires")]
+pub mod colored_wires;
+#[cfg(feature = "puzzle")]
+pub mod puzzle;
+#[cfg(feature = "terrain")]
+pub mod terrain;
+#[cfg(feature = "castle")]
+pub mod castle;
+pub mod corners;
+pub mod sockets;
\ No newline at end of file
@@ -1,9 +1,19 @@
 use crate::UndecidedTile;
 use crate::TileGrid;
+use crate::tileset;
+use crate::WfcError;
+use crate::big_tiles::BigTile;
+use crate::tileset::Layer;
 use ::rand::distributions::WeightedIndex;
 use ::rand::prelude::*;
 use std::cmp::Ordering;
 use std::hash::Hash;
+use std::collections::HashMap;
+
+/// The folder the castle's textures are in, listed in its textures.txt
+pub const TEXTURE_FOLDER: &str = "assets/castle";
+/// Where the castle's tileset is saved to and loaded from
+pub const TILESET_PATH: &str = "assets/castle/castle.tileset";
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct TileChoice {
@@ -17,10 +27,6 @@ pub struct TileChoice {
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub enum Connection {
-    DoorTop, // The side of the top of a door
-    DoorBottom, // The side of the bottom of a door
-    DoorRight, //The bottom of the right side of a door
-    DoorLeft, // The bottom of the left side of a door
     WallLeft, // The left side of a wall, where the wall is on the right
     WallRight, // The right side of a wall, where the wall is on the left
     WallTop, // The top of a wall
@@ -30,26 +36,43 @@ pub enum Connection {
 }
 
 impl Connection {
-    pub fn can_connect (con1: Connection, con2: Connection) -> bool {
+    /// Every connection, in the order they're indexed by
+    pub const ALL: [Connection; 6] = [
+        Connection::WallLeft,
+        Connection::WallRight,
+        Connection::WallTop,
+        Connection::WallInner,
+        Connection::Ground,
+        Connection::Sky,
+    ];
+
+    /// The connection's position in ALL, which is the order they're declared in
+    pub fn index (self) -> usize {
+        return self as usize;
+    }
+
+    /// The rules the tileset starts with when there's no tileset file
+    pub fn default_can_connect (con1: Connection, con2: Connection) -> bool {
         if con1 == con2 {
             return true;
         }
-        // if con2 == Connection::Ground {
-        //     return con1 == Connection::DoorRight || con1 == Connection::DoorLeft || con1 == Connection::Sky;
-        // }
-        // if con1 == Connection::Ground {
-        //     return con2 == Connection::DoorRight || con2 == Connection::DoorLeft || con2 == Connection::Sky;
-        // }
         return false;
     }
 }
 
 impl UndecidedTile {
+    /// A tile with every option in the current tileset
     pub fn new() -> Self {
+        Self {
+            possible_tiles: tileset::current().tiles.clone(),
+        }
+    }
+
+    /// The tiles the tileset starts with when there's no tileset file
+    pub fn default_tiles() -> Vec<TileChoice> {
         let mut possible_tiles = Vec::<TileChoice>::new();
 
         use Connection as c;
-        const DOOR_WEIGHT: i32 = 10;
         const WINDOW_WEIGHT: i32 = 2;
         const WALL_WEIGHT: i32 = 10;
         const EDGE_WEIGHT: i32 = 1;
@@ -57,30 +80,17 @@ impl UndecidedTile {
         const OUTER_CORNER_WEIGHT: i32 = 100;
         const INNER_CORNER_WEIGHT: i32 = 1;
 
-        // Door
-        let connections = [c::DoorLeft, c::DoorBottom, c::Ground, c::WallInner];
-        possible_tiles.push(TileChoice {connections, weight: DOOR_WEIGHT, texture: "Door L", flipx: false, flipy: false, rot90: false});
-        let connections = [c::DoorRight, c::WallInner, c::Ground, c::DoorBottom];
-        possible_tiles.push(TileChoice {connections, weight: DOOR_WEIGHT, texture: "Door R", flipx: false, flipy: false, rot90: false});
-        let connections = [c::WallInner, c::DoorTop, c::DoorLeft, c::WallInner];
-        possible_tiles.push(TileChoice {connections, weight: DOOR_WEIGHT, texture: "Door TL", flipx: false, flipy: false, rot90: false});
-        let connections = [c::WallInner, c::WallInner, c::DoorRight, c::DoorTop];
-        possible_tiles.push(TileChoice {connections, weight: DOOR_WEIGHT, texture: "Door TR", flipx: false, flipy: false, rot90: false});
-
-        // Wall and window
+        // Wall. It has two looks, which are variants rather than tiles of their own so they don't change how it collapses.
+        // They used to be separate tiles, so the weight is doubled to keep it as common as it was, and windows are on the decoration layer now
+        // so the wall takes their weight too
         let connections = [c::WallInner, c::WallInner, c::WallInner, c::WallInner];
-        possible_tiles.push(TileChoice {connections, weight: WALL_WEIGHT, texture: "Wall 1", flipx: false, flipy: false, rot90: false});
-        possible_tiles.push(TileChoice {connections, weight: WALL_WEIGHT, texture: "Wall 2", flipx: false, flipy: false, rot90: false});
-        possible_tiles.push(TileChoice {connections, weight: WINDOW_WEIGHT, texture: "Window 1", flipx: false, flipy: false, rot90: false});
-        possible_tiles.push(TileChoice {connections, weight: WINDOW_WEIGHT, texture: "Window 2", flipx: false, flipy: false, rot90: false});
+        possible_tiles.push(TileChoice {connections, weight: WALL_WEIGHT * 2 + WINDOW_WEIGHT * 2, texture: "Wall", flipx: false, flipy: false, rot90: false});
 
         // Edge
         let connections = [c::WallLeft, c::WallInner, c::WallLeft, c::Sky];
-        possible_tiles.push(TileChoice {connections, weight: EDGE_WEIGHT, texture: "Wall L1", flipx: false, flipy: false, rot90: false});
-        possible_tiles.push(TileChoice {connections, weight: EDGE_WEIGHT, texture: "Wall L2", flipx: false, flipy: false, rot90: false});
+        possible_tiles.push(TileChoice {connections, weight: EDGE_WEIGHT * 2, texture: "Wall L", flipx: false, flipy: false, rot90: false});
         let connections = [c::WallRight, c::Sky, c::WallRight, c::WallInner];
-        possible_tiles.push(TileChoice {connections, weight: EDGE_WEIGHT, texture: "Wall R1", flipx: false, flipy: false, rot90: false});
-        possible_tiles.push(TileChoice {connections, weight: EDGE_WEIGHT, texture: "Wall R2", flipx: false, flipy: false, rot90: false});
+        possible_tiles.push(TileChoice {connections, weight: EDGE_WEIGHT * 2, texture: "Wall R", flipx: false, flipy: false, rot90: false});
         let connections = [c::Sky, c::WallTop, c::WallInner, c::WallTop];
         possible_tiles.push(TileChoice {connections, weight: TOP_WEIGHT, texture: "Wall T", flipx: false, flipy: false, rot90: false});
         
@@ -98,22 +108,70 @@ impl UndecidedTile {
         let connections = [c::Sky, c::Sky, c::Sky, c::Sky];
         possible_tiles.push(TileChoice {connections, weight: 1, texture: "Sky", flipx: false, flipy: false, rot90: false});
 
+        possible_tiles
+    }
+    /// The tiles covering more than one cell the tileset starts with. The door is 2x2, with wall around it and ground under it
+    pub fn default_big_tiles() -> Vec<BigTile> {
+        use Connection as c;
+        const DOOR_WEIGHT: i32 = 10;
 
-        Self {
-            possible_tiles,
-        }
+        return vec![
+            BigTile {
+                texture: "Door",
+                width: 2,
+                height: 2,
+                weight: DOOR_WEIGHT,
+                sides: [vec![c::WallInner, c::WallInner], vec![c::WallInner, c::WallInner], vec![c::Ground, c::Ground], vec![c::WallInner, c::WallInner]],
+            },
+        ];
     }
-    pub fn collapse (&mut self) {
+
+    /// The layers the tileset starts with. Decorations go on top of the castle, with windows only on the inside of walls
+    pub fn default_layers() -> Vec<Layer> {
+        use Connection as c;
+        const NOTHING_WEIGHT: i32 = 10;
+        const WINDOW_WEIGHT: i32 = 2;
+
+        // Decorations don't care what's next to them, so the layer is free and these connections are never read
+        let connections = [c::Sky, c::Sky, c::Sky, c::Sky];
+        return vec![
+            Layer {
+                name: "Decorations",
+                free: true,
+                tiles: vec![
+                    TileChoice {connections, weight: NOTHING_WEIGHT, texture: "Nothing", flipx: false, flipy: false, rot90: false},
+                    TileChoice {connections, weight: WINDOW_WEIGHT, texture: "Window overlay", flipx: false, flipy: false, rot90: false},
+                ],
+                on: HashMap::from([("Window overlay", c::WallInner)]),
+            },
+        ];
+    }
+
+    /// The looks each texture in default_tiles can be drawn with, and how often each is used
+    pub fn default_variants() -> Vec<(&'static str, Vec<(&'static str, i32)>)> {
+        return vec![
+            ("Wall", vec![("Wall 1", 1), ("Wall 2", 1)]),
+            ("Window", vec![("Window 1", 1), ("Window 2", 1)]),
+            ("Window overlay", vec![("Window overlay 1", 1), ("Window overlay 2", 1)]),
+            ("Wall L", vec![("Wall L1", 1), ("Wall L2", 1)]),
+            ("Wall R", vec![("Wall R1", 1), ("Wall R2", 1)]),
+        ];
+    }
+    /// Picks one of the tile's options at random by weight using rng, failing if the weights can't be picked from
+    pub fn choose (&self, rng: &mut impl Rng) -> Result<TileChoice, WfcError> {
         if self.possible_tiles.len() == 1 {
-            return;
+            return Ok(self.possible_tiles[0]);
         }
         let mut weights = Vec::<i32>::new();
         for self_option in self.possible_tiles.iter() {
             weights.push(self_option.weight);
         }
-        let dist = WeightedIndex::new(&weights).unwrap();
-        let self_option = self.possible_tiles[dist.sample(&mut ::rand::thread_rng())];
-        self.possible_tiles = vec![self_option];
+        let dist = WeightedIndex::new(&weights).map_err(|_| WfcError::InvalidWeights {weights})?;
+        return Ok(self.possible_tiles[dist.sample(rng)]);
+    }
+    pub fn collapse (&mut self, rng: &mut impl Rng) -> Result<(), WfcError> {
+        self.possible_tiles = vec![self.choose(rng)?];
+        return Ok(());
     }
 }
 
@@ -155,8 +213,8 @@ impl TileGrid {
         }
         else {
             for i in 0..50 {
-                let x = ::rand::thread_rng().gen_range(0..self.width);
-                let y = ::rand::thread_rng().gen_range(0..self.height);
+                let x = self.rng.gen_range(0..self.width);
+                let y = self.rng.gen_range(0..self.height);
                 let tile = &self.tilegrid[x as usize][y as usize];
                 if tile.possible_tiles.len() == 1 {
                     continue;
@@ -183,8 +241,9 @@ impl TileGrid {
             return None;
         }
         else {
-            let dist = WeightedIndex::new(&weights).unwrap();
-            let (x_index, y_index) = candidate_indices[dist.sample(&mut ::rand::thread_rng())];
+            // RESTRICTED_WEIGHT is never 0, so this only falls back if the weights are changed to something invalid
+            let Ok(dist) = WeightedIndex::new(&weights) else {return candidate_indices.first().copied()};
+            let (x_index, y_index) = candidate_indices[dist.sample(&mut self.rng)];
             return Some((x_index, y_index));
         }
     }
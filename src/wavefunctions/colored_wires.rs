@@ -1,19 +1,35 @@
 use crate::UndecidedTile;
 use crate::TileGrid;
+use crate::tileset;
+use crate::WfcError;
+use crate::big_tiles::BigTile;
+use crate::tileset::Layer;
 use ::rand::distributions::WeightedIndex;
 use ::rand::prelude::*;
 use std::cmp::Ordering;
 use std::hash::Hash;
 
+// Coloured wires running between tiles. Every tile has the same plain texture,
+// the wires and triangles renderers draw its connections instead
+
+/// The folder the wires' background texture is in, listed in its textures.txt
+pub const TEXTURE_FOLDER: &str = "assets/colored_wires";
+/// Where the wires' tileset is saved to and loaded from
+pub const TILESET_PATH: &str = "assets/colored_wires/colored_wires.tileset";
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct TileChoice {
-    pub connections: [Connection; 4], // up down left right
+    pub connections: [Connection; 4], // up right down left
     pub weight: i32,
+    pub texture: &'static str,
+    pub flipx: bool,
+    pub flipy: bool,
+    pub rot90: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub enum Connection {
-    Black,
+    Black, // No wire
     Red,
     Green,
     Blue,
@@ -21,82 +37,103 @@ pub enum Connection {
     White,
 }
 
+/// Every colour a wire can be
+const COLORS: [Connection; 5] = [Connection::Red, Connection::Blue, Connection::Green, Connection::Yellow, Connection::White];
+
 impl Connection {
-    pub fn can_connect (con1: Connection, con2: Connection) -> bool {
+    /// Every connection, in the order they're indexed by
+    pub const ALL: [Connection; 6] = [
+        Connection::Black,
+        Connection::Red,
+        Connection::Green,
+        Connection::Blue,
+        Connection::Yellow,
+        Connection::White,
+    ];
+
+    /// The connection's position in ALL, which is the order they're declared in
+    pub fn index (self) -> usize {
+        return self as usize;
+    }
+
+    /// The rules the tileset starts with when there's no tileset file
+    pub fn default_can_connect (con1: Connection, con2: Connection) -> bool {
         return con1 == con2;
     }
 }
 
 impl UndecidedTile {
+    /// A tile with every option in the current tileset
     pub fn new() -> Self {
+        Self {
+            possible_tiles: tileset::current().tiles.clone(),
+        }
+    }
+
+    /// The tiles the tileset starts with when there's no tileset file. Each colour has every tile with exactly two or four
+    /// wires of that colour, and each pair of colours has a crossing and the two ways of turning past each other
+    pub fn default_tiles() -> Vec<TileChoice> {
         let mut possible_tiles = Vec::<TileChoice>::new();
-        for connection in [Connection::Red, Connection::Blue, Connection::Green, Connection::Yellow, Connection::White].iter() {
+
+        const WIRE_WEIGHT: i32 = 10000;
+        const CROSSING_WEIGHT: i32 = 1;
+        let tile = |connections, weight| TileChoice {connections, weight, texture: "Wires", flipx: false, flipy: false, rot90: false};
+
+        for connection in COLORS.iter() {
             for i in 0..16 {
-                let mut new_tile = TileChoice {connections: [Connection::Black; 4], weight: 1};
-                let mut conns = 0;
-                if i & 1 == 1 {
-                    new_tile.connections[0] = *connection;
-                    conns += 1;
-                }
-                if i & 2 == 2 {
-                    new_tile.connections[1] = *connection;
-                    conns += 1;
-                }
-                if i & 4 == 4 {
-                    new_tile.connections[2] = *connection;
-                    conns += 1;
-                }
-                if i & 8 == 8 {
-                    new_tile.connections[3] = *connection;
-                    conns += 1;
+                let mut connections = [Connection::Black; 4];
+                for (side, connection_side) in connections.iter_mut().enumerate() {
+                    if i & (1 << side) != 0 {
+                        *connection_side = *connection;
+                    }
                 }
+                let conns = connections.iter().filter(|side| **side != Connection::Black).count();
                 if conns != 2 && conns != 4 {
                     continue;
                 }
-                new_tile.weight = 10000;
-                possible_tiles.push(new_tile);
+                possible_tiles.push(tile(connections, WIRE_WEIGHT));
             }
         }
-            for con1 in [Connection::Red, Connection::Blue, Connection::Green, Connection::Yellow, Connection::White].iter() {
-                for con2 in [Connection::Red, Connection::Blue, Connection::Green, Connection::Yellow, Connection::White].iter() {
-                    if con1 == con2 {
-                        continue;
-                    }
-                    let mut new_tile = TileChoice {connections: [Connection::Black; 4], weight: 1};
-                    new_tile.connections[0] = *con1;
-                    new_tile.connections[1] = *con1;
-                    new_tile.connections[2] = *con2;
-                    new_tile.connections[3] = *con2;
-                    possible_tiles.push(new_tile);
-                    let mut new_tile = TileChoice {connections: [Connection::Black; 4], weight: 1};
-                    new_tile.connections[0] = *con1;
-                    new_tile.connections[1] = *con2;
-                    new_tile.connections[2] = *con2;
-                    new_tile.connections[3] = *con1;
-                    possible_tiles.push(new_tile);
-                    let mut new_tile = TileChoice {connections: [Connection::Black; 4], weight: 1};
-                    new_tile.connections[0] = *con1;
-                    new_tile.connections[1] = *con2;
-                    new_tile.connections[2] = *con1;
-                    new_tile.connections[3] = *con2;
-                    possible_tiles.push(new_tile);
+        for con1 in COLORS.iter() {
+            for con2 in COLORS.iter() {
+                if con1 == con2 {
+                    continue;
                 }
+                possible_tiles.push(tile([*con1, *con2, *con1, *con2], CROSSING_WEIGHT));
+                possible_tiles.push(tile([*con1, *con1, *con2, *con2], CROSSING_WEIGHT));
+                possible_tiles.push(tile([*con1, *con2, *con2, *con1], CROSSING_WEIGHT));
             }
-        Self {
-            possible_tiles,
         }
+
+        possible_tiles
+    }
+    /// There are no big tiles
+    pub fn default_big_tiles() -> Vec<BigTile> {
+        return Vec::new();
+    }
+    /// There are no layers
+    pub fn default_layers() -> Vec<Layer> {
+        return Vec::new();
     }
-    pub fn collapse (&mut self) {
+    /// The wires' texture has no variants
+    pub fn default_variants() -> Vec<(&'static str, Vec<(&'static str, i32)>)> {
+        return Vec::new();
+    }
+    /// Picks one of the tile's options at random by weight using rng, failing if the weights can't be picked from
+    pub fn choose (&self, rng: &mut impl Rng) -> Result<TileChoice, WfcError> {
         if self.possible_tiles.len() == 1 {
-            return;
+            return Ok(self.possible_tiles[0]);
         }
         let mut weights = Vec::<i32>::new();
         for self_option in self.possible_tiles.iter() {
             weights.push(self_option.weight);
         }
-        let dist = WeightedIndex::new(&weights).unwrap();
-        let self_option = self.possible_tiles[dist.sample(&mut ::rand::thread_rng())];
-        self.possible_tiles = vec![self_option];
+        let dist = WeightedIndex::new(&weights).map_err(|_| WfcError::InvalidWeights {weights})?;
+        return Ok(self.possible_tiles[dist.sample(rng)]);
+    }
+    pub fn collapse (&mut self, rng: &mut impl Rng) -> Result<(), WfcError> {
+        self.possible_tiles = vec![self.choose(rng)?];
+        return Ok(());
     }
 }
 
@@ -140,9 +177,10 @@ impl TileGrid {
             return None;
         }
         else {
-            let dist = WeightedIndex::new(&weights).unwrap();
-            let (x_index, y_index) = candidate_indices[dist.sample(&mut ::rand::thread_rng())];
+            // RESTRICTED_WEIGHT is never 0, so this only falls back if the weights are changed to something invalid
+            let Ok(dist) = WeightedIndex::new(&weights) else {return candidate_indices.first().copied()};
+            let (x_index, y_index) = candidate_indices[dist.sample(&mut self.rng)];
             return Some((x_index, y_index));
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,27 @@
+use crate::{TileChoice, Connection};
+use crate::tileset;
+
+// Tiles described by the terrain at each of their corners instead of a connection on each side.
+// Two tiles fit together when the corners they share have the same terrain, so which connections can connect
+// follows from the corners, and a wavefunction only has to say what's at the corners of each tile
+
+/// The terrain at each corner of a tile, clockwise from the top left: top left, top right, bottom right, bottom left
+pub type Corners<T> = [T; 4];
+
+/// The two corners along each side, up right down left, each read clockwise around the tile
+pub fn side_corners<T: Copy> (corners: Corners<T>) -> [(T, T); 4] {
+    let [top_left, top_right, bottom_right, bottom_left] = corners;
+    return [(top_left, top_right), (top_right, bottom_right), (bottom_right, bottom_left), (bottom_left, top_left)];
+}
+
+/// Whether two sides facing each other share the same corners. Each side is read clockwise around its own tile,
+/// so the corners they share come in the opposite order from the other side
+pub fn sides_match<T: PartialEq> (side1: (T, T), side2: (T, T)) -> bool {
+    return side1.0 == side2.1 && side1.1 == side2.0;
+}
+
+/// A tile with the given corners, untouched texture first, followed by each of its turns that has different corners.
+/// connection gives the connection for a side from its two corners, read clockwise, and tileopt's connections are replaced
+pub fn corner_tiles<T: Copy> (corners: Corners<T>, tileopt: TileChoice, connection: impl Fn((T, T)) -> Connection) -> Vec<TileChoice> {
+    return tileset::turns(&TileChoice {connections: side_corners(corners).map(connection), ..tileopt});
+}
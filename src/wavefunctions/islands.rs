@@ -1,10 +1,20 @@
 use crate::UndecidedTile;
 use crate::TileGrid;
+use crate::tileset;
+use crate::WfcError;
+use crate::big_tiles::BigTile;
+use crate::tileset::Layer;
+use super::corners::{corner_tiles, sides_match};
 use ::rand::distributions::WeightedIndex;
 use ::rand::prelude::*;
 use std::cmp::Ordering;
 use std::hash::Hash;
 
+/// The folder the islands' textures are in, listed in its textures.txt
+pub const TEXTURE_FOLDER: &str = "assets/islands";
+/// Where the islands' tileset is saved to and loaded from
+pub const TILESET_PATH: &str = "assets/islands/islands.tileset";
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct TileChoice {
     pub connections: [Connection; 4], // up right down left
@@ -15,96 +25,125 @@ pub struct TileChoice {
     pub rot90: bool,
 }
 
+/// What's at each corner of a tile. Beaches are drawn along the line between land and water corners
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-pub enum Connection {
+pub enum Terrain {
     Land,
     Water,
-    BeachCW,
-    BeachCCW,
+}
+
+/// The corners along a side, read clockwise around the tile, so the bottom of a beach with land at the top is WaterWater
+/// and its left side is WaterLand. Which connections connect comes from the corners, see corners.rs
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Connection {
+    LandLand,
+    LandWater,
+    WaterLand,
+    WaterWater,
 }
 
 impl Connection {
-    pub fn can_connect (con1: Connection, con2: Connection) -> bool {
-        if con1 == Connection::Land || con1 == Connection::Water {
-            return con1 == con2;
-        }
-        else if con1 == Connection::BeachCW {
-            return con2 == Connection::BeachCCW
-        }
-        else if con1 == Connection::BeachCCW {
-            return con2 == Connection::BeachCW
-        }
-        else {
-            return false;
-        }
+    /// Every connection, in the order they're indexed by
+    pub const ALL: [Connection; 4] = [
+        Connection::LandLand,
+        Connection::LandWater,
+        Connection::WaterLand,
+        Connection::WaterWater,
+    ];
+
+    /// The connection for a side with these corners, read clockwise
+    pub fn from_corners (corners: (Terrain, Terrain)) -> Self {
+        use Terrain as t;
+        return match corners {
+            (t::Land, t::Land) => Connection::LandLand,
+            (t::Land, t::Water) => Connection::LandWater,
+            (t::Water, t::Land) => Connection::WaterLand,
+            (t::Water, t::Water) => Connection::WaterWater,
+        };
+    }
+
+    /// The corners at either end of the side, read clockwise
+    pub fn corners (self) -> (Terrain, Terrain) {
+        use Terrain as t;
+        return match self {
+            Connection::LandLand => (t::Land, t::Land),
+            Connection::LandWater => (t::Land, t::Water),
+            Connection::WaterLand => (t::Water, t::Land),
+            Connection::WaterWater => (t::Water, t::Water),
+        };
+    }
+
+    /// The connection's position in ALL, which is the order they're declared in
+    pub fn index (self) -> usize {
+        return self as usize;
+    }
+
+    /// Two sides connect when they share the same corners
+    pub fn default_can_connect (con1: Connection, con2: Connection) -> bool {
+        return sides_match(con1.corners(), con2.corners());
     }
 }
 
 impl UndecidedTile {
+    /// A tile with every option in the current tileset
     pub fn new() -> Self {
+        Self {
+            possible_tiles: tileset::current().tiles.clone(),
+        }
+    }
+
+    /// The tiles the tileset starts with when there's no tileset file. Each texture is given by its corners the way it's drawn,
+    /// and its turns are added with the connections worked out from the turned corners
+    pub fn default_tiles() -> Vec<TileChoice> {
         let mut possible_tiles = Vec::<TileChoice>::new();
 
-        use Connection as c;
+        use Terrain as t;
         const BEACH_WEIGHT: i32 = 10;
         const BEACH_WATER_WEIGHT: i32 = 1;
         const BEACH_LAND_WEIGHT: i32 = 1;
         const LAND_WEIGHT: i32 = 30;
         const WATER_WEIGHT: i32 = 50;
 
-        // Straight Beaches
-        let connections = [c::Land, c::BeachCCW, c::Water, c::BeachCW];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WEIGHT, texture: "beach", flipx: false, flipy: false, rot90: false});
-        let connections = [c::BeachCW, c::Land, c::BeachCCW, c::Water];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WEIGHT, texture: "beach", flipx: false, flipy: false, rot90: true});
-        let connections = [c::Water, c::BeachCW, c::Land, c::BeachCCW];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WEIGHT, texture: "beach", flipx: true, flipy: true, rot90: false});
-        let connections = [c::BeachCCW, c::Water, c::BeachCW, c::Land];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WEIGHT, texture: "beach", flipx: true, flipy: true, rot90: true});
-
-        // Watery Corners
-
-        
-
-        let connections = [c::BeachCCW, c::Water, c::Water, c::BeachCW];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WATER_WEIGHT, texture: "beach_water_corner", flipx: false, flipy: false, rot90: false});
-        let connections = [c::BeachCW, c::BeachCCW, c::Water, c::Water];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WATER_WEIGHT, texture: "beach_water_corner", flipx: false, flipy: false, rot90: true});
-        let connections = [c::Water, c::BeachCW, c::BeachCCW, c::Water];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WATER_WEIGHT, texture: "beach_water_corner", flipx: true, flipy: true, rot90: false});
-        let connections = [c::Water, c::Water, c::BeachCW, c::BeachCCW];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_WATER_WEIGHT, texture: "beach_water_corner", flipx: true, flipy: true, rot90: true});
-
-        // Land Corners
-        let connections = [c::Land, c::Land, c::BeachCCW, c::BeachCW];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_LAND_WEIGHT, texture: "beach_land_corner", flipx: false, flipy: false, rot90: false});
-        let connections = [c::BeachCW, c::Land, c::Land, c::BeachCCW];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_LAND_WEIGHT, texture: "beach_land_corner", flipx: false, flipy: false, rot90: true});
-        let connections = [c::BeachCCW, c::BeachCW, c::Land, c::Land];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_LAND_WEIGHT, texture: "beach_land_corner", flipx: true, flipy: true, rot90: false});
-        let connections = [c::Land, c::BeachCCW, c::BeachCW, c::Land];
-        possible_tiles.push(TileChoice {connections, weight: BEACH_LAND_WEIGHT, texture: "beach_land_corner", flipx: true, flipy: true, rot90: true});
-
-        // Land and Water
-        let connections = [c::Land, c::Land, c::Land, c::Land];
-        possible_tiles.push(TileChoice {connections, weight: LAND_WEIGHT, texture: "land", flipx: false, flipy: false, rot90: false});
-        let connections = [c::Water, c::Water, c::Water, c::Water];
-        possible_tiles.push(TileChoice {connections, weight: WATER_WEIGHT, texture: "water", flipx: false, flipy: false, rot90: false});
+        let tile = |weight, texture| TileChoice {connections: [Connection::LandLand; 4], weight, texture, flipx: false, flipy: false, rot90: false};
+        // Straight beaches, with land along the top
+        possible_tiles.extend(corner_tiles([t::Land, t::Land, t::Water, t::Water], tile(BEACH_WEIGHT, "beach"), Connection::from_corners));
+        // Watery corners, with land only in the top left
+        possible_tiles.extend(corner_tiles([t::Land, t::Water, t::Water, t::Water], tile(BEACH_WATER_WEIGHT, "beach_water_corner"), Connection::from_corners));
+        // Land corners, with water only in the bottom left
+        possible_tiles.extend(corner_tiles([t::Land, t::Land, t::Land, t::Water], tile(BEACH_LAND_WEIGHT, "beach_land_corner"), Connection::from_corners));
+        // Land and water
+        possible_tiles.extend(corner_tiles([t::Land; 4], tile(LAND_WEIGHT, "land"), Connection::from_corners));
+        possible_tiles.extend(corner_tiles([t::Water; 4], tile(WATER_WEIGHT, "water"), Connection::from_corners));
 
-        Self {
-            possible_tiles,
-        }
+        possible_tiles
     }
-    pub fn collapse (&mut self) {
+    /// There are no big tiles
+    pub fn default_big_tiles() -> Vec<BigTile> {
+        return Vec::new();
+    }
+    /// There are no layers
+    pub fn default_layers() -> Vec<Layer> {
+        return Vec::new();
+    }
+    /// The islands' textures have no variants
+    pub fn default_variants() -> Vec<(&'static str, Vec<(&'static str, i32)>)> {
+        return Vec::new();
+    }
+    /// Picks one of the tile's options at random by weight using rng, failing if the weights can't be picked from
+    pub fn choose (&self, rng: &mut impl Rng) -> Result<TileChoice, WfcError> {
         if self.possible_tiles.len() == 1 {
-            return;
+            return Ok(self.possible_tiles[0]);
         }
         let mut weights = Vec::<i32>::new();
         for self_option in self.possible_tiles.iter() {
             weights.push(self_option.weight);
         }
-        let dist = WeightedIndex::new(&weights).unwrap();
-        let self_option = self.possible_tiles[dist.sample(&mut ::rand::thread_rng())];
-        self.possible_tiles = vec![self_option];
+        let dist = WeightedIndex::new(&weights).map_err(|_| WfcError::InvalidWeights {weights})?;
+        return Ok(self.possible_tiles[dist.sample(rng)]);
+    }
+    pub fn collapse (&mut self, rng: &mut impl Rng) -> Result<(), WfcError> {
+        self.possible_tiles = vec![self.choose(rng)?];
+        return Ok(());
     }
 }
 
@@ -146,8 +185,8 @@ impl TileGrid {
         }
         else {
             for i in 0..50 {
-                let x = ::rand::thread_rng().gen_range(0..self.width);
-                let y = ::rand::thread_rng().gen_range(0..self.height);
+                let x = self.rng.gen_range(0..self.width);
+                let y = self.rng.gen_range(0..self.height);
                 let tile = &self.tilegrid[x as usize][y as usize];
                 if tile.possible_tiles.len() == 1 {
                     continue;
@@ -174,9 +213,46 @@ impl TileGrid {
             return None;
         }
         else {
-            let dist = WeightedIndex::new(&weights).unwrap();
-            let (x_index, y_index) = candidate_indices[dist.sample(&mut ::rand::thread_rng())];
+            // RESTRICTED_WEIGHT is never 0, so this only falls back if the weights are changed to something invalid
+            let Ok(dist) = WeightedIndex::new(&weights) else {return candidate_indices.first().copied()};
+            let (x_index, y_index) = candidate_indices[dist.sample(&mut self.rng)];
             return Some((x_index, y_index));
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Connection as c;
+    use Terrain as t;
+
+    fn beach () -> TileChoice {
+        return TileChoice {connections: [c::LandLand; 4], weight: 1, texture: "beach", flipx: false, flipy: false, rot90: false};
+    }
+
+    #[test]
+    fn a_beach_has_four_turns_with_their_corners_turned () {
+        let tiles = corner_tiles([t::Land, t::Land, t::Water, t::Water], beach(), Connection::from_corners);
+        let turns: Vec<([Connection; 4], bool, bool, bool)> = tiles.iter().map(|tile| (tile.connections, tile.flipx, tile.flipy, tile.rot90)).collect();
+        assert_eq!(turns, vec![
+            ([c::LandLand, c::LandWater, c::WaterWater, c::WaterLand], false, false, false),
+            ([c::WaterLand, c::LandLand, c::LandWater, c::WaterWater], false, false, true),
+            ([c::WaterWater, c::WaterLand, c::LandLand, c::LandWater], true, true, false),
+            ([c::LandWater, c::WaterWater, c::WaterLand, c::LandLand], true, true, true),
+        ]);
+    }
+
+    #[test]
+    fn tiles_with_the_same_corners_all_round_only_have_one_turn () {
+        assert_eq!(corner_tiles([t::Land; 4], beach(), Connection::from_corners).len(), 1);
+    }
+
+    #[test]
+    fn sides_match_when_they_share_corners () {
+        // Beaches side by side, each side read clockwise around its own tile
+        assert!(Connection::default_can_connect(c::LandWater, c::WaterLand));
+        assert!(Connection::default_can_connect(c::LandLand, c::LandLand));
+        assert!(!Connection::default_can_connect(c::LandWater, c::LandWater));
+        assert!(!Connection::default_can_connect(c::LandLand, c::WaterWater));
+    }
+}
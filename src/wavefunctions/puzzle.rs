@@ -1,10 +1,20 @@
 use crate::UndecidedTile;
 use crate::TileGrid;
+use crate::tileset;
+use crate::WfcError;
+use crate::big_tiles::BigTile;
+use crate::tileset::Layer;
+use super::sockets::{self, Socket};
 use ::rand::distributions::WeightedIndex;
 use ::rand::prelude::*;
 use std::cmp::Ordering;
 use std::hash::Hash;
 
+/// The folder the puzzle pieces' textures are in, listed in its textures.txt
+pub const TEXTURE_FOLDER: &str = "assets/puzzle";
+/// Where the puzzle's tileset is saved to and loaded from
+pub const TILESET_PATH: &str = "assets/puzzle/puzzle.tileset";
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct TileChoice {
     pub connections: [Connection; 4], // up right down left
@@ -15,102 +25,100 @@ pub struct TileChoice {
     pub rot90: bool,
 }
 
+/// The shape of a piece's edge along one segment of a side
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-pub enum Connection {
-    OrangeOut,
-    OrangeIn,
-    OrangeFlat,
-    PurpleOut,
-    PurpleIn,
-    PurpleFlat,
-    GreenOut,
-    GreenIn,
-    GreenFlat,
-    YellowOut,
-    YellowIn,
-    YellowFlat,
+pub enum Shape {
+    Flat,
+    /// Sticks out into the next piece
+    Tab,
+    /// Cut into the piece, for a tab to go in
+    Hole,
 }
 
+/// Every shape, in the order sockets are counted in
+const SHAPES: [Shape; 3] = [Shape::Flat, Shape::Tab, Shape::Hole];
+/// How many segments each side is split into
+const SEGMENTS: usize = 3;
+
+impl sockets::Segment for Shape {
+    /// Tabs go in holes, and holes and flat edges can sit against flat edges
+    fn fits (self, other: Shape) -> bool {
+        return matches!((self, other),
+            (Shape::Tab, Shape::Hole) | (Shape::Hole, Shape::Tab)
+            | (Shape::Flat, Shape::Flat) | (Shape::Flat, Shape::Hole) | (Shape::Hole, Shape::Flat));
+    }
+}
+
+/// A side of a piece, as the shape of each segment along it read clockwise
+pub type Connection = Socket<Shape, SEGMENTS>;
+
 impl Connection {
-    pub fn can_connect (con1: Connection, con2: Connection) -> bool {
-        let outs = [Connection::OrangeOut, Connection::PurpleOut, Connection::GreenOut, Connection::YellowOut];
-        let ins = [Connection::OrangeIn, Connection::PurpleIn, Connection::GreenIn, Connection::YellowIn];
-        let flats = [Connection::OrangeFlat, Connection::PurpleFlat, Connection::GreenFlat, Connection::YellowFlat];
-        if outs.contains(&con1) {
-            return ins.contains(&con2);
-        }
-        else if ins.contains(&con1) {
-            return outs.contains(&con2) || flats.contains(&con2);
-        }
-        else if flats.contains(&con1) {
-            return flats.contains(&con2) || ins.contains(&con2);
-        }
-        else {
-            return false;
-        }
+    /// Every socket, in the order they're indexed by
+    pub const ALL: [Connection; 27] = Socket::all(SHAPES);
+
+    pub fn index (self) -> usize {
+        return self.position(&SHAPES);
+    }
+
+    /// Two sides connect when every segment fits the one facing it
+    pub fn default_can_connect (con1: Connection, con2: Connection) -> bool {
+        return con1.fits(con2);
     }
 }
 
 impl UndecidedTile {
+    /// A tile with every option in the current tileset
     pub fn new() -> Self {
-        let mut possible_tiles = Vec::<TileChoice>::new();
-        
-        use Connection as c;
-        //Orange
-        let connections = [c::OrangeFlat, c::OrangeOut, c::OrangeIn, c::OrangeFlat];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "1", flipx: false, flipy: false, rot90: false});
-        let connections = [c::OrangeFlat, c::OrangeFlat, c::OrangeOut, c::OrangeIn];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "1", flipx: false, flipy: false, rot90: true});
-        let connections = [c::OrangeIn, c::OrangeFlat, c::OrangeFlat, c::OrangeOut];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "1", flipx: true, flipy: true, rot90: false});
-        let connections = [c::OrangeOut, c::OrangeIn, c::OrangeFlat, c::OrangeFlat];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "1", flipx: true, flipy: true, rot90: true});
-
-        // Purple
-        let connections = [c::PurpleOut, c::PurpleOut, c::PurpleIn, c::PurpleFlat];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "2", flipx: false, flipy: false, rot90: false});
-        let connections = [c::PurpleFlat, c::PurpleOut, c::PurpleOut, c::PurpleIn];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "2", flipx: false, flipy: false, rot90: true});
-        let connections = [c::PurpleIn, c::PurpleFlat, c::PurpleOut, c::PurpleOut];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "2", flipx: true, flipy: true, rot90: false});
-        let connections = [c::PurpleOut, c::PurpleIn, c::PurpleFlat, c::PurpleOut];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "2", flipx: true, flipy: true, rot90: true});
-
-        // Green
-        let connections = [c::GreenOut, c::GreenOut, c::GreenOut, c::GreenFlat];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "3", flipx: false, flipy: false, rot90: false});
-        let connections = [c::GreenFlat, c::GreenOut, c::GreenOut, c::GreenOut];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "3", flipx: false, flipy: false, rot90: true});
-        let connections = [c::GreenOut, c::GreenFlat, c::GreenOut, c::GreenOut];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "3", flipx: true, flipy: true, rot90: false});
-        let connections = [c::GreenOut, c::GreenOut, c::GreenFlat, c::GreenOut];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "3", flipx: true, flipy: true, rot90: true});
-
-        // Yellow
-        let connections = [c::YellowIn, c::YellowIn, c::YellowFlat, c::YellowFlat];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "4", flipx: false, flipy: false, rot90: false});
-        let connections = [c::YellowFlat, c::YellowIn, c::YellowIn, c::YellowFlat];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "4", flipx: false, flipy: false, rot90: true});
-        let connections = [c::YellowFlat, c::YellowFlat, c::YellowIn, c::YellowIn];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "4", flipx: true, flipy: true, rot90: false});
-        let connections = [c::YellowIn, c::YellowFlat, c::YellowFlat, c::YellowIn];
-        possible_tiles.push(TileChoice {connections, weight: 1, texture: "4", flipx: true, flipy: true, rot90: true});
-
         Self {
-            possible_tiles,
+            possible_tiles: tileset::current().tiles.clone(),
         }
     }
-    pub fn collapse (&mut self) {
+
+    /// The tiles the tileset starts with when there's no tileset file, each piece and its turns.
+    /// Pieces only have a tab or a hole in the middle of a side, the segments either side of it are flat
+    pub fn default_tiles() -> Vec<TileChoice> {
+        let mut possible_tiles = Vec::<TileChoice>::new();
+
+        use Shape as s;
+        let flat = Socket([s::Flat, s::Flat, s::Flat]);
+        let tab = Socket([s::Flat, s::Tab, s::Flat]);
+        let hole = Socket([s::Flat, s::Hole, s::Flat]);
+        let piece = |connections, texture| TileChoice {connections, weight: 1, texture, flipx: false, flipy: false, rot90: false};
+
+        possible_tiles.extend(tileset::turns(&piece([flat, tab, hole, flat], "1")));
+        possible_tiles.extend(tileset::turns(&piece([tab, tab, hole, flat], "2")));
+        possible_tiles.extend(tileset::turns(&piece([tab, tab, tab, flat], "3")));
+        possible_tiles.extend(tileset::turns(&piece([hole, hole, flat, flat], "4")));
+
+        possible_tiles
+    }
+    /// There are no big tiles
+    pub fn default_big_tiles() -> Vec<BigTile> {
+        return Vec::new();
+    }
+    /// There are no layers
+    pub fn default_layers() -> Vec<Layer> {
+        return Vec::new();
+    }
+    /// The pieces' textures have no variants
+    pub fn default_variants() -> Vec<(&'static str, Vec<(&'static str, i32)>)> {
+        return Vec::new();
+    }
+    /// Picks one of the tile's options at random by weight using rng, failing if the weights can't be picked from
+    pub fn choose (&self, rng: &mut impl Rng) -> Result<TileChoice, WfcError> {
         if self.possible_tiles.len() == 1 {
-            return;
+            return Ok(self.possible_tiles[0]);
         }
         let mut weights = Vec::<i32>::new();
         for self_option in self.possible_tiles.iter() {
             weights.push(self_option.weight);
         }
-        let dist = WeightedIndex::new(&weights).unwrap();
-        let self_option = self.possible_tiles[dist.sample(&mut ::rand::thread_rng())];
-        self.possible_tiles = vec![self_option];
+        let dist = WeightedIndex::new(&weights).map_err(|_| WfcError::InvalidWeights {weights})?;
+        return Ok(self.possible_tiles[dist.sample(rng)]);
+    }
+    pub fn collapse (&mut self, rng: &mut impl Rng) -> Result<(), WfcError> {
+        self.possible_tiles = vec![self.choose(rng)?];
+        return Ok(());
     }
 }
 
@@ -152,8 +160,8 @@ impl TileGrid {
         }
         else {
             for i in 0..50 {
-                let x = ::rand::thread_rng().gen_range(0..self.width);
-                let y = ::rand::thread_rng().gen_range(0..self.height);
+                let x = self.rng.gen_range(0..self.width);
+                let y = self.rng.gen_range(0..self.height);
                 let tile = &self.tilegrid[x as usize][y as usize];
                 if tile.possible_tiles.len() == 1 {
                     continue;
@@ -180,8 +188,9 @@ impl TileGrid {
             return None;
         }
         else {
-            let dist = WeightedIndex::new(&weights).unwrap();
-            let (x_index, y_index) = candidate_indices[dist.sample(&mut ::rand::thread_rng())];
+            // RESTRICTED_WEIGHT is never 0, so this only falls back if the weights are changed to something invalid
+            let Ok(dist) = WeightedIndex::new(&weights) else {return candidate_indices.first().copied()};
+            let (x_index, y_index) = candidate_indices[dist.sample(&mut self.rng)];
             return Some((x_index, y_index));
         }
     }
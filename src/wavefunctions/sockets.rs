@@ -0,0 +1,112 @@
+use std::fmt;
+
+// Sockets made of several segments along a side instead of one value per side, so something like a door on the left half
+// of a top edge is a segment pattern rather than a connection of its own. A side is read clockwise around its tile,
+// so the side facing it is read the other way, and two sides fit when each segment fits the one opposite it
+
+/// One piece of a side
+pub trait Segment: Copy + PartialEq + fmt::Debug {
+    /// Whether this segment can sit against the other. Order must not matter
+    fn fits (self, other: Self) -> bool;
+}
+
+/// A side made of N segments, read clockwise around the tile. Wavefunctions can use a Socket as their Connection
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Socket<S, const N: usize> (pub [S; N]);
+
+impl<S: Segment, const N: usize> Socket<S, N> {
+    /// Whether two sides facing each other fit. The other side is read the opposite way to this one, so it's reversed first
+    pub fn fits (self, other: Self) -> bool {
+        return self.0.iter().zip(other.0.iter().rev()).all(|(a, b)| a.fits(*b));
+    }
+
+    /// Every socket that can be made from the segments, which is what Connection::ALL should be.
+    /// COUNT has to be the number of segments to the power of N. Sockets are in order of position(segments)
+    pub const fn all<const SEGMENTS: usize, const COUNT: usize> (segments: [S; SEGMENTS]) -> [Self; COUNT] {
+        assert!(COUNT == SEGMENTS.pow(N as u32), "COUNT has to be SEGMENTS to the power of N");
+        let mut all = [Socket([segments[0]; N]); COUNT];
+        let mut i = 0;
+        while i < COUNT {
+            // Each socket is i written in base SEGMENTS, with the first segment as the biggest digit
+            let mut rest = i;
+            let mut k = N;
+            while k > 0 {
+                k -= 1;
+                all[i].0[k] = segments[rest % SEGMENTS];
+                rest /= SEGMENTS;
+            }
+            i += 1;
+        }
+        return all;
+    }
+
+    /// Where the socket is in all(segments)
+    pub fn position (self, segments: &[S]) -> usize {
+        return self.0.iter().fold(0, |index, segment| {
+            index * segments.len() + segments.iter().position(|other| other == segment).unwrap_or(0)
+        });
+    }
+}
+
+/// Written as the segments joined by dashes, like Flat-Tab-Flat, which is also how it's named in tileset files
+impl<S: Segment, const N: usize> fmt::Debug for Socket<S, N> {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<String> = self.0.iter().map(|segment| format!("{:?}", segment)).collect();
+        return write!(f, "{}", names.join("-"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    enum Shape {
+        Flat,
+        Tab,
+        Hole,
+    }
+
+    impl Segment for Shape {
+        fn fits (self, other: Self) -> bool {
+            return matches!((self, other), (Shape::Flat, Shape::Flat) | (Shape::Tab, Shape::Hole) | (Shape::Hole, Shape::Tab));
+        }
+    }
+
+    const SHAPES: [Shape; 3] = [Shape::Flat, Shape::Tab, Shape::Hole];
+
+    #[test]
+    fn sides_are_compared_against_the_other_side_reversed () {
+        use Shape::*;
+        // A tab on the left of one side faces the right of the other side
+        assert!(Socket([Tab, Flat, Flat]).fits(Socket([Flat, Flat, Hole])));
+        assert!(!Socket([Tab, Flat, Flat]).fits(Socket([Hole, Flat, Flat])));
+        assert!(Socket([Tab, Hole, Flat]).fits(Socket([Flat, Tab, Hole])));
+        assert!(!Socket([Tab, Tab, Tab]).fits(Socket([Tab, Tab, Tab])));
+    }
+
+    #[test]
+    fn all_has_every_socket_once () {
+        let all: [Socket<Shape, 3>; 27] = Socket::all(SHAPES);
+        assert_eq!(all.len(), 3usize.pow(3));
+        for (i, a) in all.iter().enumerate() {
+            assert!(all[i + 1..].iter().all(|b| a != b));
+        }
+        assert_eq!(all[0], Socket([Shape::Flat; 3]));
+        assert_eq!(all[1], Socket([Shape::Flat, Shape::Flat, Shape::Tab]));
+        assert_eq!(all[26], Socket([Shape::Hole; 3]));
+    }
+
+    #[test]
+    fn position_is_where_the_socket_is_in_all () {
+        let all: [Socket<Shape, 3>; 27] = Socket::all(SHAPES);
+        for (i, socket) in all.iter().enumerate() {
+            assert_eq!(socket.position(&SHAPES), i);
+        }
+    }
+
+    #[test]
+    fn sockets_are_named_by_their_segments () {
+        assert_eq!(format!("{:?}", Socket([Shape::Flat, Shape::Tab, Shape::Hole])), "Flat-Tab-Hole");
+    }
+}
@@ -0,0 +1,188 @@
+use crate::UndecidedTile;
+use crate::TileGrid;
+use crate::tileset;
+use crate::WfcError;
+use crate::big_tiles::BigTile;
+use crate::tileset::Layer;
+use ::rand::distributions::WeightedIndex;
+use ::rand::prelude::*;
+use std::cmp::Ordering;
+use std::hash::Hash;
+
+// A coarse terrain map for the autotile renderer to draw, where each tile is only a kind of ground.
+// The rules just keep land away from water, and the coastline's shape is left to the autotiler,
+// so there are three tiles instead of the fourteen islands needs to spell out every beach
+
+/// The terrain's textures, which are shared with islands
+pub const TEXTURE_FOLDER: &str = "assets/islands";
+/// Where the terrain's tileset is saved to and loaded from
+pub const TILESET_PATH: &str = "assets/islands/terrain.tileset";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct TileChoice {
+    pub connections: [Connection; 4], // up right down left
+    pub weight: i32,
+    pub texture: &'static str,
+    pub flipx: bool,
+    pub flipy: bool,
+    pub rot90: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Connection {
+    Land,
+    Sand,
+    Water,
+}
+
+impl Connection {
+    /// Every connection, in the order they're indexed by
+    pub const ALL: [Connection; 3] = [
+        Connection::Land,
+        Connection::Sand,
+        Connection::Water,
+    ];
+
+    /// The connection's position in ALL, which is the order they're declared in
+    pub fn index (self) -> usize {
+        return self as usize;
+    }
+
+    /// Sand goes next to anything, but land and water always have sand between them
+    pub fn default_can_connect (con1: Connection, con2: Connection) -> bool {
+        if con1 == con2 {
+            return true;
+        }
+        return con1 == Connection::Sand || con2 == Connection::Sand;
+    }
+}
+
+impl UndecidedTile {
+    /// A tile with every option in the current tileset
+    pub fn new() -> Self {
+        Self {
+            possible_tiles: tileset::current().tiles.clone(),
+        }
+    }
+
+    /// One tile for each terrain, named the same as the terrain so the autotile renderer can tell which it is
+    pub fn default_tiles() -> Vec<TileChoice> {
+        let mut possible_tiles = Vec::<TileChoice>::new();
+
+        use Connection as c;
+        const LAND_WEIGHT: i32 = 40;
+        const SAND_WEIGHT: i32 = 20;
+        const WATER_WEIGHT: i32 = 40;
+
+        let connections = [c::Land; 4];
+        possible_tiles.push(TileChoice {connections, weight: LAND_WEIGHT, texture: "land", flipx: false, flipy: false, rot90: false});
+        let connections = [c::Sand; 4];
+        possible_tiles.push(TileChoice {connections, weight: SAND_WEIGHT, texture: "sand", flipx: false, flipy: false, rot90: false});
+        let connections = [c::Water; 4];
+        possible_tiles.push(TileChoice {connections, weight: WATER_WEIGHT, texture: "water", flipx: false, flipy: false, rot90: false});
+
+        possible_tiles
+    }
+    /// There are no big tiles
+    pub fn default_big_tiles() -> Vec<BigTile> {
+        return Vec::new();
+    }
+    /// There are no layers
+    pub fn default_layers() -> Vec<Layer> {
+        return Vec::new();
+    }
+    /// The terrain is drawn by the autotiler, so none of its textures have variants
+    pub fn default_variants() -> Vec<(&'static str, Vec<(&'static str, i32)>)> {
+        return Vec::new();
+    }
+    /// Picks one of the tile's options at random by weight using rng, failing if the weights can't be picked from
+    pub fn choose (&self, rng: &mut impl Rng) -> Result<TileChoice, WfcError> {
+        if self.possible_tiles.len() == 1 {
+            return Ok(self.possible_tiles[0]);
+        }
+        let mut weights = Vec::<i32>::new();
+        for self_option in self.possible_tiles.iter() {
+            weights.push(self_option.weight);
+        }
+        let dist = WeightedIndex::new(&weights).map_err(|_| WfcError::InvalidWeights {weights})?;
+        return Ok(self.possible_tiles[dist.sample(rng)]);
+    }
+    pub fn collapse (&mut self, rng: &mut impl Rng) -> Result<(), WfcError> {
+        self.possible_tiles = vec![self.choose(rng)?];
+        return Ok(());
+    }
+}
+
+impl TileGrid {
+    pub fn pick_index(&mut self) -> Option<(i32, i32)> {
+        let mut candidate_indices = Vec::<(i32, i32)>::new();
+        let mut least_seen = 100000;
+        let mut weights = Vec::<i32>::new();
+        let mut total_seen = 0;
+        const RESTRICTED_WEIGHT: i32 = 1; //when this is high, it will prioritize tiles with the least options. Cannot be 0
+        const FREE_WEIGHT: i32 = 000; //when this is high, it will prioritize tiles that don't have the least options
+        // restricted_weight is good for when the ruleset is restrictive (such as "all tiles must have precisely 2 connections"), and for making large blocks
+        // free_weight is good for when you want smaller, more scattered blocks
+        if self.width * self.height <= 1000 {
+            for i in 0..self.width {
+                for j in 0..self.height {
+                    let tile = &self.tilegrid[i as usize][j as usize];
+                    if tile.possible_tiles.len() == 1 {
+                        continue;
+                    }
+                    total_seen += 1;
+    
+                    match tile.possible_tiles.len().cmp(&least_seen) {
+                        Ordering::Less => {
+                            least_seen = tile.possible_tiles.len();
+                            weights = vec![FREE_WEIGHT;total_seen-1];
+                            weights.push(RESTRICTED_WEIGHT);
+                        }
+                        Ordering::Equal => {
+                            weights.push(RESTRICTED_WEIGHT);
+                        },
+                        Ordering::Greater => {
+                            weights.push(FREE_WEIGHT);
+                        },
+                    }
+                    candidate_indices.push((i, j));
+                }
+            }
+        }
+        else {
+            for i in 0..50 {
+                let x = self.rng.gen_range(0..self.width);
+                let y = self.rng.gen_range(0..self.height);
+                let tile = &self.tilegrid[x as usize][y as usize];
+                if tile.possible_tiles.len() == 1 {
+                    continue;
+                }
+                total_seen += 1;
+                match tile.possible_tiles.len().cmp(&least_seen) {
+                    Ordering::Less => {
+                        least_seen = tile.possible_tiles.len();
+                        weights = vec![FREE_WEIGHT;total_seen-1];
+                        weights.push(RESTRICTED_WEIGHT);
+                    }
+                    Ordering::Equal => {
+                        weights.push(RESTRICTED_WEIGHT);
+                    },
+                    Ordering::Greater => {
+                        weights.push(FREE_WEIGHT);
+                    },
+                }
+                candidate_indices.push((x, y));
+            }
+        }
+
+        if candidate_indices.is_empty() {
+            return None;
+        }
+        else {
+            // RESTRICTED_WEIGHT is never 0, so this only falls back if the weights are changed to something invalid
+            let Ok(dist) = WeightedIndex::new(&weights) else {return candidate_indices.first().copied()};
+            let (x_index, y_index) = candidate_indices[dist.sample(&mut self.rng)];
+            return Some((x_index, y_index));
+        }
+    }
+}
\ No newline at end of file